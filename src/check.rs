@@ -0,0 +1,469 @@
+//! `patter check`: finds calls whose arguments can't match the parameters of
+//! the function being called, using the patterns from `evals_to` as types.
+
+use std::fmt;
+use std::fmt::Display;
+
+use crate::context::{Bindings, Context};
+use crate::exhaust;
+use crate::infer::{with_bindings, StaticMatch};
+use crate::intern::Interned;
+use crate::parse;
+use crate::{Fun, Ident, SExpr};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Severity {
+    /// The call can never succeed
+    Definite,
+    /// The call might not succeed
+    Possible,
+}
+
+#[derive(Clone, Debug)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub call: SExpr,
+    pub problem: Problem,
+}
+
+#[derive(Clone, Debug)]
+pub enum Problem {
+    /// The arguments don't match the parameters of the callee
+    Args {
+        /// The patterns inferred for the arguments
+        args: Box<SExpr>,
+        /// The `args_ptn` of each clause of the callee
+        params: Vec<SExpr>,
+    },
+    /// The arguments match, but the intrinsic being called fails with every
+    /// value they can have
+    Fails {
+        /// The patterns inferred for the arguments
+        args: Box<SExpr>,
+    },
+    /// Values of the matched expression that no arm of a `match` matches
+    Missing(Vec<SExpr>),
+    /// The arm of a `match` at this index only matches what earlier ones do
+    Unreachable(usize),
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let level = match self.severity {
+            Severity::Definite => "error",
+            Severity::Possible => "warning",
+        };
+        match &self.problem {
+            Problem::Args { args, params } => {
+                let verb = match self.severity {
+                    Severity::Definite => "never match",
+                    Severity::Possible => "might not match",
+                };
+                write!(
+                    f,
+                    "{}: the arguments of {} {} its parameters\n  arguments: {}\n  parameters:",
+                    level, self.call, verb, args
+                )?;
+                for params in params {
+                    write!(f, " {}", params)?;
+                }
+                Ok(())
+            }
+            Problem::Fails { args } => write!(
+                f,
+                "{}: {} always fails\n  arguments: {}",
+                level, self.call, args
+            ),
+            Problem::Missing(missing) => {
+                write!(f, "{}: {} has no arm for some values\n  missing:", level, self.call)?;
+                for ptn in missing {
+                    write!(f, " {}", ptn)?;
+                }
+                Ok(())
+            }
+            Problem::Unreachable(arm) => write!(
+                f,
+                "{}: arm {} of {} can't be reached, as earlier arms match everything it does",
+                level, arm, self.call
+            ),
+        }
+    }
+}
+
+/// A place written more than once in the same pattern, which then only
+/// matches equal values
+#[derive(Clone, Debug)]
+pub struct DuplicatePlace {
+    pub place: Interned<'static, Ident>,
+    pub ptn: SExpr,
+}
+
+impl Display for DuplicatePlace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "warning: ,{} is bound more than once in {}, so it only matches equal values",
+            self.place, self.ptn
+        )
+    }
+}
+
+/// Checks the top level forms of a program, looking up names that it doesn't
+/// define in `cxt`. Calls that might not match are only reported if
+/// `possible` is set; problems with the arms of a `match` always are.
+pub fn check_program(
+    forms: &[SExpr],
+    cxt: &Context,
+    possible: bool,
+) -> Vec<Diagnostic> {
+    let mut checker = Checker {
+        possible,
+        diagnostics: Vec::new(),
+    };
+    let mut defs = Bindings::empty();
+    for form in forms {
+        if let Some((name, value)) = as_def(form) {
+            let ptn = value.evals_to(&|id| {
+                defs.get(id)
+                    .cloned()
+                    .or_else(|| cxt.lookup(id).map(|v| v.literal_ptn()))
+            });
+            defs.insert(Bindings::of(name, &ptn));
+        }
+        if let Some((name, clauses)) = as_extension(form) {
+            let ptn = extended_ptn(name, clauses, &|id| {
+                defs.get(id)
+                    .cloned()
+                    .or_else(|| cxt.lookup(id).map(|v| v.literal_ptn()))
+            });
+            defs.insert(Bindings::of(name, &ptn));
+        }
+        checker.check_expr(form, &|id| {
+            defs.get(id)
+                .cloned()
+                .or_else(|| cxt.lookup(id).map(|v| v.literal_ptn()))
+        });
+    }
+    checker.diagnostics
+}
+
+/// Finds the patterns in the top level forms of a program that bind a place
+/// more than once. Every argument of a call that isn't a call itself is taken
+/// to be a pattern.
+pub fn duplicate_places(forms: &[SExpr]) -> Vec<DuplicatePlace> {
+    let mut found = Vec::new();
+    for form in forms {
+        find_duplicates(form, &mut found);
+    }
+    found
+}
+
+/// Runs `patter check [--possible] [--duplicates] <file>` against the
+/// standard library, returning the exit code
+pub fn main(args: &[String]) -> i32 {
+    let possible = args.iter().any(|a| a == "--possible");
+    let duplicates = args.iter().any(|a| a == "--duplicates");
+    let path = match args.iter().find(|a| !a.starts_with("--")) {
+        Some(path) => path,
+        None => {
+            eprintln!("usage: patter check [--possible] [--duplicates] <file>");
+            return 2;
+        }
+    };
+    let source = match std::fs::read_to_string(path) {
+        Ok(source) => source,
+        Err(e) => {
+            eprintln!("{}: {}", path, e);
+            return 2;
+        }
+    };
+    let forms = match patter!(&format!("({})", source)) {
+        SExpr::List(forms) => forms,
+        _ => unreachable!(),
+    };
+    let diagnostics = check_program(&forms, &crate::STD_CXT, possible);
+    for diagnostic in &diagnostics {
+        println!("{}: {}", path, diagnostic);
+    }
+    if duplicates {
+        for duplicate in duplicate_places(&forms) {
+            println!("{}: {}", path, duplicate);
+        }
+    }
+    if diagnostics.iter().any(|d| d.severity == Severity::Definite) {
+        1
+    } else {
+        0
+    }
+}
+
+struct Checker {
+    possible: bool,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Checker {
+    fn check_expr(
+        &mut self,
+        expr: &SExpr,
+        env: &dyn Fn(Interned<'static, Ident>) -> Option<SExpr>,
+    ) {
+        use SExpr::*;
+        match expr {
+            // Inner calls first, so that a call failing is reported there
+            // rather than at every call it is an argument of
+            List(ls) => {
+                self.check_siblings(ls, env);
+                self.check_call(ls, env);
+                if let Some(Ident(head)) = ls.first() {
+                    if *head == ident!("match") {
+                        self.check_match(ls, env);
+                    }
+                }
+            }
+            UnarySigilApp('[', inner) => {
+                if let List(ls) = &**inner {
+                    self.check_siblings(ls, env)
+                }
+            }
+            UnarySigilApp('`', inner) => self.check_expr(inner, env),
+            _ => {}
+        }
+    }
+
+    /// Checks a list of expressions. Quoted code among them is usually the
+    /// body of a function, match arm or the like, so it is checked with the
+    /// places the others bind known, though not what they'll match.
+    fn check_siblings(
+        &mut self,
+        exprs: &[SExpr],
+        env: &dyn Fn(Interned<'static, Ident>) -> Option<SExpr>,
+    ) {
+        let places = Bindings::of_contents(
+            exprs
+                .iter()
+                .flat_map(bound_places)
+                .map(|id| (id, SExpr::any()))
+                .collect(),
+        );
+        let quoted_env = with_bindings(&places, env);
+        for expr in exprs {
+            match expr {
+                SExpr::UnarySigilApp('`', inner) => {
+                    self.check_expr(inner, &quoted_env)
+                }
+                expr => self.check_expr(expr, env),
+            }
+        }
+    }
+
+    fn check_call(
+        &mut self,
+        call: &[SExpr],
+        env: &dyn Fn(Interned<'static, Ident>) -> Option<SExpr>,
+    ) {
+        let (head, args) = match call.split_first() {
+            Some(it) => it,
+            None => return,
+        };
+        let fun = match head.evals_to(env).as_literal() {
+            Some(SExpr::Fun(fun)) => fun,
+            _ => return,
+        };
+        let args = args.iter().map(|a| a.evals_to(env)).collect::<Vec<_>>();
+        // An argument that evaluates to nothing fails before the call is
+        // made, and is reported itself
+        if args.iter().any(SExpr::is_nothing) {
+            return;
+        }
+        let args = SExpr::List(args).simplify();
+        let clauses = fun.all_clauses();
+        let matches = clauses
+            .iter()
+            .map(|c| c.args_ptn.bind_static(&args))
+            .collect::<Vec<_>>();
+        let severity = if matches
+            .iter()
+            .all(|m| matches!(m, StaticMatch::Never))
+        {
+            Severity::Definite
+        } else if fun.is_intrinsic()
+            && args.as_literal().is_some()
+            // Only pure intrinsics are folded, so this is the call failing
+            // with these arguments rather than for lack of the environment
+            && SExpr::List(call.to_vec()).evals_to(env).is_nothing()
+        {
+            self.diagnostics.push(Diagnostic {
+                severity: Severity::Definite,
+                call: SExpr::List(call.to_vec()),
+                problem: Problem::Fails {
+                    args: Box::new(args),
+                },
+            });
+            return;
+        } else if matches.iter().any(|m| matches!(m, StaticMatch::Always(_))) {
+            return;
+        } else if self.possible {
+            Severity::Possible
+        } else {
+            return;
+        };
+        self.diagnostics.push(Diagnostic {
+            severity,
+            call: SExpr::List(call.to_vec()),
+            problem: Problem::Args {
+                args: Box::new(args),
+                params: clauses.into_iter().map(|c| *c.args_ptn).collect(),
+            },
+        });
+    }
+
+    /// Checks that a `match` has an arm for every value of what it matches,
+    /// and that each of its arms can be reached. Matches whose arms aren't
+    /// known statically are skipped.
+    fn check_match(
+        &mut self,
+        call: &[SExpr],
+        env: &dyn Fn(Interned<'static, Ident>) -> Option<SExpr>,
+    ) {
+        let (value, arms) = match call {
+            [_, value, arms @ ..] => (value, arms),
+            _ => return,
+        };
+        let mut ptns = Vec::new();
+        for arm in arms {
+            match arm.evals_to(env).as_literal() {
+                Some(SExpr::List(arm)) if arm.len() == 2 => {
+                    ptns.push(arm[0].clone())
+                }
+                _ => return,
+            }
+        }
+        let call = SExpr::List(call.to_vec());
+        let missing = exhaust::missing(&ptns, &value.evals_to(env));
+        if !missing.is_empty() {
+            self.diagnostics.push(Diagnostic {
+                severity: Severity::Possible,
+                call: call.clone(),
+                problem: Problem::Missing(missing),
+            });
+        }
+        for arm in exhaust::redundant(&ptns) {
+            self.diagnostics.push(Diagnostic {
+                severity: Severity::Possible,
+                call: call.clone(),
+                problem: Problem::Unreachable(arm),
+            });
+        }
+    }
+}
+
+/// `(def ,name value)`
+fn as_def(form: &SExpr) -> Option<(Interned<'static, Ident>, &SExpr)> {
+    match form {
+        SExpr::List(ls) => match ls.as_slice() {
+            [SExpr::Ident(def), SExpr::UnarySigilApp(',', name), value]
+                if *def == ident!("def") =>
+            {
+                Some((name.clone().as_ident()?, value))
+            }
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// The name of the function and the clauses of `(extend f clause...)`
+fn as_extension(form: &SExpr) -> Option<(Interned<'static, Ident>, &[SExpr])> {
+    match form {
+        SExpr::List(ls) => match ls.as_slice() {
+            [SExpr::Ident(extend), SExpr::Ident(name), clauses @ ..]
+                if *extend == ident!("extend") =>
+            {
+                Some((*name, clauses))
+            }
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// The pattern of the function `name` once `clauses` are added to it, which
+/// is `any` unless all of them are known
+fn extended_ptn(
+    name: Interned<'static, Ident>,
+    clauses: &[SExpr],
+    env: &dyn Fn(Interned<'static, Ident>) -> Option<SExpr>,
+) -> SExpr {
+    let mut extended = match env(name).and_then(|p| p.as_literal()) {
+        Some(SExpr::Fun(fun)) => fun.clauses(),
+        _ => return SExpr::any(),
+    };
+    for clause in clauses {
+        match clause.evals_to(env).as_literal() {
+            Some(SExpr::Fun(fun)) => extended.extend(fun.clauses()),
+            _ => return SExpr::any(),
+        }
+    }
+    match Fun::from_clauses(extended) {
+        Some(fun) => SExpr::Fun(fun).literal_ptn(),
+        None => SExpr::any(),
+    }
+}
+
+/// The places written directly (outside of quotes) in an expression
+fn bound_places(expr: &SExpr) -> Vec<Interned<'static, Ident>> {
+    use SExpr::*;
+    match expr {
+        UnarySigilApp(',', name) => {
+            name.clone().as_ident().into_iter().collect()
+        }
+        UnarySigilApp('`', _) => vec![],
+        UnarySigilApp(_, inner) => bound_places(inner),
+        List(ls) => ls.iter().flat_map(bound_places).collect(),
+        _ => vec![],
+    }
+}
+
+fn find_duplicates(expr: &SExpr, found: &mut Vec<DuplicatePlace>) {
+    use SExpr::*;
+    match expr {
+        List(ls) => {
+            for e in ls {
+                if let List(_) = e {
+                    find_duplicates(e, found);
+                    continue;
+                }
+                let places = bound_places(e);
+                for (i, place) in places.iter().enumerate() {
+                    if places[..i].contains(place)
+                        && !found
+                            .iter()
+                            .any(|d| d.place == *place && d.ptn == *e)
+                    {
+                        found.push(DuplicatePlace {
+                            place: *place,
+                            ptn: e.clone(),
+                        });
+                    }
+                }
+                for quoted in quoted_parts(e) {
+                    find_duplicates(quoted, found);
+                }
+            }
+        }
+        UnarySigilApp('`', inner) => find_duplicates(inner, found),
+        _ => {}
+    }
+}
+
+/// The quoted expressions in an expression, outside of other quotes
+fn quoted_parts(expr: &SExpr) -> Vec<&SExpr> {
+    use SExpr::*;
+    match expr {
+        UnarySigilApp('`', inner) => vec![inner],
+        UnarySigilApp(_, inner) => quoted_parts(inner),
+        List(ls) => ls.iter().flat_map(quoted_parts).collect(),
+        _ => vec![],
+    }
+}
@@ -1,13 +1,19 @@
+use std::cmp::Ordering;
 use std::collections::HashMap;
-use std::convert::{TryFrom, TryInto};
+use std::convert::TryFrom;
 use std::fmt;
 use std::fmt::Display;
 use std::iter::Extend;
+use std::sync::atomic::{self, AtomicUsize};
 
+use crate::error::InterpreterError;
+use crate::infer::{const_fold, with_bindings, StaticMatch};
 use crate::intern::Interned;
+use crate::number::{Number, Precision, Rounding};
 use crate::parse;
 use crate::Ident;
 use crate::SExpr;
+use crate::SExprKind;
 use crate::IDENTS;
 use crate::{FromSExpr, IntoSExpr};
 
@@ -16,9 +22,31 @@ pub struct Context {
     contexts: Vec<ContextInner>,
 }
 
+/// How many scopes have been pushed, so that each gets its own id
+static SCOPES: AtomicUsize = AtomicUsize::new(0);
+
 #[derive(Clone, Debug)]
 struct ContextInner {
     pub bindings: Bindings,
+    pub kind: ScopeKind,
+    /// What scope handles refer to this scope by. Module scopes are 0, and
+    /// other scopes are numbered from 1 as they're pushed, so a handle to a
+    /// scope that has been popped refers to no other.
+    pub id: usize,
+}
+
+/// What pushed a scope. Scope handles are resolved by skipping over the
+/// scopes pushed for calls to intrinsics, so wrapping a definition in more
+/// (or fewer) primitive calls doesn't change where it lands.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScopeKind {
+    Module,
+    Closure,
+    Args,
+    Block,
+    Intrinsic,
+    /// Pushed while a macro is expanding a call to it
+    Macro,
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -31,12 +59,28 @@ impl Bindings {
         self
     }
 
+    /// Joins bindings from parts of the same pattern, which must agree on
+    /// the values of the places they both bind
+    pub fn unify(mut self, other: &Bindings) -> Option<Bindings> {
+        for (id, value) in &other.0 {
+            match self.0.get(id) {
+                Some(bound) if *id != ident!("#/noread") && bound != value => {
+                    return None
+                }
+                _ => {
+                    self.0.insert(*id, value.clone());
+                }
+            }
+        }
+        Some(self)
+    }
+
     pub fn intersect(
         left: Option<Bindings>,
         right: Option<Bindings>,
     ) -> Option<Bindings> {
         if let (Some(l_binds), Some(r_binds)) = (left, right) {
-            Some(l_binds.join(&r_binds))
+            l_binds.unify(&r_binds)
         } else {
             None
         }
@@ -50,6 +94,16 @@ impl Bindings {
         Bindings(HashMap::new())
     }
 
+    pub fn get(&self, ident: Interned<'static, Ident>) -> Option<&SExpr> {
+        self.0.get(&ident)
+    }
+
+    pub fn iter(
+        &self,
+    ) -> impl Iterator<Item = (&Interned<'static, Ident>, &SExpr)> {
+        self.0.iter()
+    }
+
     pub fn basic() -> Bindings {
         Bindings::empty()
             .join(&Bindings::of(
@@ -69,6 +123,9 @@ impl Bindings {
                         ident!("q-expr"),
                     )])),
                     closure: Box::new(Bindings::empty()),
+                    next_clause: None,
+                    id: crate::Fun::fresh_id(),
+                    extensions: crate::Extensions::default(),
                 }),
             ))
             .join(&Bindings::of(
@@ -85,18 +142,24 @@ impl Bindings {
                                 Interned<'static, Ident>,
                             )
                                 -> Option<SExpr>| {
-                                SExpr::LitMatch(Box::new(SExpr::Place(
-                                    cxt(ident!("ptn-ident"))
-                                        .unwrap()
-                                        .as_ident()
-                                        .unwrap(),
-                                )))
+                                match cxt(ident!("ptn-ident"))
+                                    .and_then(|p| p.as_literal())
+                                    .and_then(SExpr::as_ident)
+                                {
+                                    Some(id) => SExpr::LitMatch(Box::new(
+                                        SExpr::Place(id),
+                                    )),
+                                    None => SExpr::any(),
+                                }
                             },
                     }),
                     args_ptn: Box::new(SExpr::List(vec![SExpr::Place(
                         ident!("ptn-ident"),
                     )])),
                     closure: Box::new(Bindings::empty()),
+                    next_clause: None,
+                    id: crate::Fun::fresh_id(),
+                    extensions: crate::Extensions::default(),
                 }),
             ))
             .join(&Bindings::of(
@@ -109,7 +172,7 @@ impl Bindings {
                                     .as_list()
                                     .unwrap()
                                     .iter()
-                                    .map(|e| e.eval(&mut cxt))
+                                    .map(|e| e.expand(&mut cxt)?.eval(&mut cxt))
                                     .collect::<Result<_, _>>()?,
                             ))
                         },
@@ -118,23 +181,25 @@ impl Bindings {
                                 Interned<'static, Ident>,
                             )
                                 -> Option<SExpr>| {
-                                SExpr::List(
-                                    cxt(ident!("brk-list"))
-                                        .unwrap()
-                                        .as_list()
-                                        .unwrap()
-                                        .iter()
-                                        .map(|e| e.clone().as_ident().unwrap())
-                                        .map(cxt)
-                                        .collect::<Option<_>>()
-                                        .unwrap(),
-                                )
+                                match cxt(ident!("brk-list"))
+                                    .and_then(|l| l.as_literal())
+                                {
+                                    Some(SExpr::List(ls)) => SExpr::List(
+                                        ls.iter()
+                                            .map(|e| e.evals_to(cxt))
+                                            .collect(),
+                                    ),
+                                    _ => SExpr::List(vec![SExpr::many_any()]),
+                                }
                             },
                     }),
                     args_ptn: Box::new(SExpr::List(vec![SExpr::Place(
                         ident!("brk-list"),
                     )])),
                     closure: Box::new(Bindings::empty()),
+                    next_clause: None,
+                    id: crate::Fun::fresh_id(),
+                    extensions: crate::Extensions::default(),
                 }),
             ))
     }
@@ -144,75 +209,494 @@ impl Bindings {
             .join(primitive!(
                 "#/add",
                 "[,lhs ,rhs]",
-                Number(get!("lhs", cxt)
-                    .as_number()
-                    .expect(&format!("Can only add numbers, not {:?}", get!("lhs", cxt)))
-                    + get!("rhs", cxt)
-                        .as_number()
-                    .expect(&format!("Can only add numbers, not {:?}", get!("rhs", cxt)))),
-                Place(ident!("#/noread")),
+                arithmetic(cxt, &["lhs", "rhs"], |ns| ns[0].checked_add(&ns[1]), "Can only add rational numbers")?,
+                const_fold(eval, &["lhs", "rhs"], cxt),
+                cxt
+            ))
+            .join(primitive!(
+                "#/sub",
+                "[,lhs ,rhs]",
+                arithmetic(cxt, &["lhs", "rhs"], |ns| ns[0].checked_sub(&ns[1]), "Can only subtract rational numbers")?,
+                const_fold(eval, &["lhs", "rhs"], cxt),
+                cxt
+            ))
+            .join(primitive!(
+                "#/mul",
+                "[,lhs ,rhs]",
+                arithmetic(cxt, &["lhs", "rhs"], |ns| ns[0].checked_mul(&ns[1]), "Can only multiply rational numbers")?,
+                const_fold(eval, &["lhs", "rhs"], cxt),
+                cxt
+            ))
+            .join(primitive!(
+                "#/div",
+                "[,lhs ,rhs]",
+                arithmetic(cxt, &["lhs", "rhs"], |ns| ns[0].checked_div(&ns[1]), "Can only divide rational numbers by nonzero ones")?,
+                const_fold(eval, &["lhs", "rhs"], cxt),
+                cxt
+            ))
+            .join(primitive!(
+                "#/quot",
+                "[,lhs ,rhs]",
+                arithmetic(cxt, &["lhs", "rhs"], |ns| ns[0].checked_quot(&ns[1]), "Can only divide rational numbers by nonzero ones")?,
+                const_fold(eval, &["lhs", "rhs"], cxt),
+                cxt
+            ))
+            .join(primitive!(
+                "#/rem",
+                "[,lhs ,rhs]",
+                arithmetic(cxt, &["lhs", "rhs"], |ns| ns[0].checked_rem(&ns[1]), "Can only divide rational numbers by nonzero ones")?,
+                const_fold(eval, &["lhs", "rhs"], cxt),
+                cxt
+            ))
+            .join(primitive!(
+                "#/mod",
+                "[,lhs ,rhs]",
+                arithmetic(cxt, &["lhs", "rhs"], |ns| ns[0].checked_mod(&ns[1]), "Can only divide rational numbers by nonzero ones")?,
+                const_fold(eval, &["lhs", "rhs"], cxt),
+                cxt
+            ))
+            .join(primitive!(
+                "#/pow",
+                "[,lhs ,rhs]",
+                arithmetic(cxt, &["lhs", "rhs"], |ns| ns[0].checked_pow(&ns[1]), "Can only raise rational numbers to integer powers, and zero to nonnegative ones")?,
+                const_fold(eval, &["lhs", "rhs"], cxt),
+                cxt
+            ))
+            .join(primitive!(
+                "#/neg",
+                "[,n]",
+                arithmetic(cxt, &["n"], |ns| ns[0].checked_neg(), "Can only negate rational numbers")?,
+                const_fold(eval, &["n"], cxt),
+                cxt
+            ))
+            .join(primitive!(
+                "#/abs",
+                "[,n]",
+                arithmetic(cxt, &["n"], |ns| ns[0].checked_abs(), "Can only take the absolute value of rational numbers")?,
+                const_fold(eval, &["n"], cxt),
+                cxt
+            ))
+            .join(primitive!(
+                "#/float/make",
+                "[,x ,base ,significands ,exponents ,rounding]",
+                {
+                    let precision = float_precision(cxt)?;
+                    let rounding = Rounding::from_sexpr(get!("rounding", cxt))?;
+                    arithmetic(cxt, &["x"], |ns| ns[0].to_float(&precision, rounding), "Out of the range of the float precision, or its significands are unbounded")?
+                },
+                const_fold(eval, &["x", "base", "significands", "exponents", "rounding"], cxt),
+                cxt
+            ))
+            .join(primitive!(
+                "#/float/add",
+                "[,lhs ,rhs ,rounding]",
+                rounded_arithmetic(cxt, crate::number::Number::add_rounded, "Out of the range of the float precision, or its significands are unbounded")?,
+                const_fold(eval, &["lhs", "rhs", "rounding"], cxt),
+                cxt
+            ))
+            .join(primitive!(
+                "#/float/sub",
+                "[,lhs ,rhs ,rounding]",
+                rounded_arithmetic(cxt, crate::number::Number::sub_rounded, "Out of the range of the float precision, or its significands are unbounded")?,
+                const_fold(eval, &["lhs", "rhs", "rounding"], cxt),
+                cxt
+            ))
+            .join(primitive!(
+                "#/float/mul",
+                "[,lhs ,rhs ,rounding]",
+                rounded_arithmetic(cxt, crate::number::Number::mul_rounded, "Out of the range of the float precision, or its significands are unbounded")?,
+                const_fold(eval, &["lhs", "rhs", "rounding"], cxt),
+                cxt
+            ))
+            .join(primitive!(
+                "#/float/div",
+                "[,lhs ,rhs ,rounding]",
+                rounded_arithmetic(cxt, crate::number::Number::div_rounded, "Can only divide numbers by nonzero ones")?,
+                const_fold(eval, &["lhs", "rhs", "rounding"], cxt),
+                cxt
+            ))
+            .join(primitive!(
+                "#/float/to-rational",
+                "[,x]",
+                arithmetic(cxt, &["x"], |ns| ns[0].to_exact(), "Not a float with an exact value")?,
+                const_fold(eval, &["x"], cxt),
+                cxt
+            ))
+            .join(primitive!(
+                "#/lt",
+                "[,lhs ,rhs]",
+                (compare(cxt)? == Ordering::Less).into_sexpr(),
+                const_fold(eval, &["lhs", "rhs"], cxt),
+                cxt
+            ))
+            .join(primitive!(
+                "#/le",
+                "[,lhs ,rhs]",
+                (compare(cxt)? != Ordering::Greater).into_sexpr(),
+                const_fold(eval, &["lhs", "rhs"], cxt),
+                cxt
+            ))
+            .join(primitive!(
+                "#/gt",
+                "[,lhs ,rhs]",
+                (compare(cxt)? == Ordering::Greater).into_sexpr(),
+                const_fold(eval, &["lhs", "rhs"], cxt),
+                cxt
+            ))
+            .join(primitive!(
+                "#/ge",
+                "[,lhs ,rhs]",
+                (compare(cxt)? != Ordering::Less).into_sexpr(),
+                const_fold(eval, &["lhs", "rhs"], cxt),
+                cxt
+            ))
+            .join(primitive!(
+                "#/num-eq",
+                "[,lhs ,rhs]",
+                (compare(cxt)? == Ordering::Equal).into_sexpr(),
+                const_fold(eval, &["lhs", "rhs"], cxt),
+                cxt
+            ))
+            .join(primitive!(
+                "#/compare",
+                "[,lhs ,rhs]",
+                compare(cxt)?.into_sexpr(),
+                const_fold(eval, &["lhs", "rhs"], cxt),
                 cxt
             ))
             .join(primitive!(
                 "#/with?",
-                "[,ptn ,expr ,consec ,alt ,scope-depth]",
+                "[,ptn ,expr ,consec ,alt ,scope]",
                {
+                    let scope = get!("scope", cxt)
+                        .as_scope()
+                        .ok_or(interpreter_err!(NotA, SExprKind::Scope, get!("scope", cxt)))?;
                     if let Some(bindings) = get!("ptn", cxt).match_ptn(&get!("expr", cxt))? {
-                        let depth = get!("scope-depth", cxt)
-                            .as_number()
-                            .unwrap()
-                            .try_into()
-                            .unwrap();
-                        cxt.add_bindings_at_depth(&bindings, depth);
-                        get!("consec", cxt).eval(&mut cxt)?
+                        let consec = get!("consec", cxt);
+                        cxt.add_bindings_in(&bindings, scope)?;
+                        cxt.in_scope(scope, |cxt| consec.expand(cxt)?.eval(cxt))?
                     } else {
-                        get!("alt", cxt).eval(&mut cxt)?
+                        let alt = get!("alt", cxt);
+                        cxt.in_scope(scope, |cxt| alt.expand(cxt)?.eval(cxt))?
                     }
                 },
-                //TODO Make this better
-                /*patter_sr!(
-                    patter_std!("ptn/union/make").unwrap().as_fun().unwrap(),
-                    List(vec![
-                        cxt(ident!("consec")).unwrap().evals_to(),
-                        cxt(ident!("alt")).unwrap().evals_to()
-                    ]),
-                    &mut Context::empty()
-            ).unwrap(),*/
-                Place(ident!("#/noread")),
+                {
+                    let branch = |name, env: &dyn Fn(Interned<'static, crate::Ident>) -> Option<SExpr>| {
+                        cxt(ident!(name))
+                            .and_then(|code| code.as_literal())
+                            .map_or_else(SExpr::any, |code| code.evals_to(env))
+                    };
+                    let expr = cxt(ident!("expr")).unwrap_or_else(SExpr::any);
+                    match cxt(ident!("ptn")).and_then(|p| p.as_literal()).map(|p| p.bind_static(&expr)) {
+                        Some(StaticMatch::Never) => branch("alt", cxt),
+                        Some(StaticMatch::Always(b)) => branch("consec", &with_bindings(&b, cxt)),
+                        Some(StaticMatch::Maybe(b)) => SExpr::union(vec![
+                            branch("consec", &with_bindings(&b, cxt)),
+                            branch("alt", cxt),
+                        ]),
+                        None => SExpr::union(vec![branch("consec", cxt), branch("alt", cxt)]),
+                    }
+                },
+                cxt
+            ))
+            .join(primitive!(
+                "#/rewrite",
+                "[,rules ,order ,rewrite-expr ,scope]",
+                {
+                    let scope = get!("scope", cxt)
+                        .as_scope()
+                        .ok_or(interpreter_err!(NotA, SExprKind::Scope, get!("scope", cxt)))?;
+                    let rules = get!("rules", cxt)
+                        .as_list()
+                        .ok_or(interpreter_err!(NotA, SExprKind::List, get!("rules", cxt)))?
+                        .into_iter()
+                        .map(crate::rewrite::Rule::from_sexpr)
+                        .collect::<Result<Vec<_>, _>>()?;
+                    let order = crate::rewrite::Order::from_sexpr(get!("order", cxt))?;
+                    let expr = get!("rewrite-expr", cxt);
+                    crate::rewrite::rewrite(&rules, order, expr, cxt, scope)?
+                },
+                SExpr::any(),
                 cxt
             ))
             .join(primitive!(
                 "#/fun/make",
                 "[,fun-expr ,args-ptn]",
-                Fun(crate::Fun{
-                    body: Box::new(get!("fun-expr", defn_cxt)),
-                    args_ptn: Box::new(get!("args-ptn", defn_cxt)),
-                    closure: Box::new(
-                        defn_cxt.collapse_keeping_sorted(
-                            get!("fun-expr", defn_cxt).referenced_idents()
-                        )
-                    ),
-                }),
-                //TODO Make this better
-                Place(ident!("#/noread")),
+                {
+                    let body = get!("fun-expr", defn_cxt).expand(defn_cxt)?;
+                    Fun(crate::Fun{
+                        args_ptn: Box::new(get!("args-ptn", defn_cxt)),
+                        closure: Box::new(
+                            defn_cxt.collapse_keeping_sorted(body.referenced_idents())
+                        ),
+                        body: Box::new(body),
+                        next_clause: None,
+                        id: crate::Fun::fresh_id(),
+                        extensions: crate::Extensions::default(),
+                    })
+                },
+                {
+                    // Built without expanding the body, which could run macros
+                    let literal =
+                        |name| defn_cxt(ident!(name)).and_then(|p| p.as_literal());
+                    match (literal("fun-expr"), literal("args-ptn")) {
+                        (Some(body), Some(args_ptn)) => Fun(crate::Fun{
+                            args_ptn: Box::new(args_ptn),
+                            closure: Box::new(
+                                Context::basic()
+                                    .collapse_keeping_sorted(body.referenced_idents())
+                            ),
+                            body: Box::new(body),
+                            next_clause: None,
+                            id: crate::Fun::fresh_id(),
+                            extensions: crate::Extensions::default(),
+                        }).literal_ptn(),
+                        _ => SExpr::any(),
+                    }
+                },
                 defn_cxt
             ))
+            .join(primitive!(
+                "#/fun/clauses",
+                "[,funs]",
+                {
+                    let funs = list(cxt, "funs")?;
+                    let clauses = funs
+                        .iter()
+                        .map(|f| Ok(crate::Fun::from_sexpr(f.clone())?.all_clauses()))
+                        .collect::<Result<Vec<_>, InterpreterError>>()?
+                        .concat();
+                    Fun(crate::Fun::from_clauses(clauses).ok_or(interpreter_err!(
+                        CannotConvert,
+                        "A function needs at least one clause",
+                        get!("funs", cxt)
+                    ))?)
+                },
+                const_fold(eval, &["funs"], cxt),
+                cxt
+            ))
+            .join(primitive!(
+                "#/fun/extend",
+                "[,fun ,clauses]",
+                {
+                    let fun = crate::Fun::from_sexpr(get!("fun", cxt))?;
+                    let clauses = list(cxt, "clauses")?
+                        .iter()
+                        .map(|f| Ok(crate::Fun::from_sexpr(f.clone())?.all_clauses()))
+                        .collect::<Result<Vec<_>, InterpreterError>>()?
+                        .concat();
+                    fun.extensions.add(clauses);
+                    UnarySigilApp(':', Box::new(List(vec![])))
+                },
+                UnarySigilApp(':', Box::new(List(vec![]))),
+                cxt
+            ))
             .join(primitive!(
                 "#/defnever",
-                "[,name]",
+                "[,name ,scope]",
                 {
-                    cxt.add_bindings_at_depth(
+                    let scope = get!("scope", cxt)
+                        .as_scope()
+                        .ok_or(interpreter_err!(NotA, SExprKind::Scope, get!("scope", cxt)))?;
+                    cxt.add_bindings_in(
                         &Bindings::of(
                             get!("name", cxt).as_ident().unwrap(),
                             &Never
                         ),
-                        4
-                    );
+                        scope
+                    )?;
                     UnarySigilApp(':', Box::new(List(vec![])))
                 },
                 UnarySigilApp(':', Box::new(List(vec![]))),
                 cxt
             ))
+            .join(primitive!(
+                "#/match",
+                "[,match-expr ,arms ,scope]",
+                {
+                    let scope = get!("scope", cxt)
+                        .as_scope()
+                        .ok_or(interpreter_err!(NotA, SExprKind::Scope, get!("scope", cxt)))?;
+                    let expr = get!("match-expr", cxt);
+                    let mut tried = Vec::new();
+                    let mut chosen = None;
+                    for arm in list(cxt, "arms")? {
+                        let (ptn, body) = match arm.clone().as_list().as_deref() {
+                            Some([ptn, body]) => (ptn.clone(), body.clone()),
+                            _ => throw_interpreter_err!(
+                                CannotConvert,
+                                "Match arms must be [pattern body] pairs",
+                                arm
+                            ),
+                        };
+                        if let Some(bindings) = ptn.match_ptn(&expr)? {
+                            chosen = Some((bindings, body));
+                            break;
+                        }
+                        tried.push(ptn);
+                    }
+                    match chosen {
+                        Some((bindings, body)) => {
+                            cxt.eval_in_block(scope, &bindings, &body)?
+                        }
+                        None => throw_interpreter_err!(NoMatchingArm, expr, tried),
+                    }
+                },
+                {
+                    let expr = cxt(ident!("match-expr")).unwrap_or_else(SExpr::any);
+                    let arms = match cxt(ident!("arms")).and_then(|a| a.as_literal()) {
+                        Some(List(arms)) => arms,
+                        _ => return SExpr::any(),
+                    };
+                    let mut results = Vec::new();
+                    for arm in arms {
+                        let (ptn, body) = match arm.as_list().as_deref() {
+                            Some([ptn, body]) => (ptn.clone(), body.clone()),
+                            _ => continue,
+                        };
+                        match ptn.bind_static(&expr) {
+                            StaticMatch::Never => {}
+                            StaticMatch::Maybe(b) => {
+                                results.push(body.evals_to(&with_bindings(&b, cxt)))
+                            }
+                            StaticMatch::Always(b) => {
+                                results.push(body.evals_to(&with_bindings(&b, cxt)));
+                                break;
+                            }
+                        }
+                    }
+                    SExpr::union(results)
+                },
+                cxt
+            ))
+            .join(primitive!(
+                "#/let",
+                "[,let-binds ,body ,scope]",
+                {
+                    let scope = get!("scope", cxt)
+                        .as_scope()
+                        .ok_or(interpreter_err!(NotA, SExprKind::Scope, get!("scope", cxt)))?;
+                    let binds = list(cxt, "let-binds")?;
+                    let body = get!("body", cxt);
+                    cxt.in_scope(scope, |cxt| {
+                        cxt.push_scope(ScopeKind::Block);
+                        let result = try {
+                            for bind in binds {
+                                let (ptn, value) = match bind.clone().as_list().as_deref() {
+                                    Some([ptn, value]) => (
+                                        ptn.expand(cxt)?.eval(cxt)?,
+                                        value.expand(cxt)?.eval(cxt)?,
+                                    ),
+                                    _ => throw_interpreter_err!(
+                                        CannotConvert,
+                                        "Let bindings must be (pattern value) pairs",
+                                        bind
+                                    ),
+                                };
+                                match ptn.match_ptn(&value)? {
+                                    Some(bindings) => cxt.add_bindings(&bindings),
+                                    None => throw_interpreter_err!(NoMatchingArm, value, vec![ptn]),
+                                }
+                            }
+                            body.expand(cxt)?.eval(cxt)?
+                        };
+                        cxt.pop_scope();
+                        result
+                    })?
+                },
+                {
+                    let (binds, body) = match (
+                        cxt(ident!("let-binds")).and_then(|b| b.as_literal()),
+                        cxt(ident!("body")).and_then(|b| b.as_literal()),
+                    ) {
+                        (Some(List(binds)), Some(body)) => (binds, body),
+                        _ => return SExpr::any(),
+                    };
+                    let mut bound = Bindings::empty();
+                    for bind in binds {
+                        let (ptn, value) = match bind.as_list().as_deref() {
+                            Some([ptn, value]) => {
+                                let env = with_bindings(&bound, cxt);
+                                (ptn.evals_to(&env), value.evals_to(&env))
+                            }
+                            _ => return SExpr::nothing(),
+                        };
+                        let matched = match ptn.as_literal() {
+                            Some(ptn) => ptn.bind_static(&value),
+                            None => return SExpr::any(),
+                        };
+                        match matched {
+                            StaticMatch::Always(b) | StaticMatch::Maybe(b) => bound.insert(b),
+                            StaticMatch::Never => return SExpr::nothing(),
+                        }
+                    }
+                    let env = with_bindings(&bound, cxt);
+                    body.evals_to(&env)
+                },
+                cxt
+            ))
+            .join(primitive!(
+                "#/scope/current",
+                "[]",
+                Scope(cxt.current_scope()),
+                Place(ident!("#/noread")),
+                cxt
+            ))
+            .join(primitive!(
+                "#/scope/caller",
+                "[]",
+                Scope(cxt.caller_scope()),
+                Place(ident!("#/noread")),
+                cxt
+            ))
+            .join(primitive!(
+                "#/scope/module",
+                "[]",
+                Scope(cxt.module_scope()),
+                Place(ident!("#/noread")),
+                cxt
+            ))
+            .join(primitive!(
+                "#/quasiquote",
+                "[,qq-expr]",
+                {
+                    let template = get!("qq-expr", cxt);
+                    if cxt.in_macro() {
+                        template.hygienic().quasiquote(1, cxt)?
+                    } else {
+                        template.quasiquote(1, cxt)?
+                    }
+                },
+                {
+                    // Without unquotes, it's the same as a quote
+                    let unquotes = |code: SExpr| {
+                        code.subtrees().iter().any(|e| matches!(e, UnarySigilApp('@', _)))
+                    };
+                    match cxt(ident!("qq-expr")) {
+                        Some(code) if code.as_literal().is_some_and(|c| !unquotes(c)) => code,
+                        _ => SExpr::any(),
+                    }
+                },
+                cxt
+            ))
+            .join(primitive!(
+                "#/macro/make",
+                "[,fun]",
+                Macro(
+                    get!("fun", cxt)
+                        .as_fun()
+                        .ok_or(interpreter_err!(NotA, SExprKind::Fun, get!("fun", cxt)))?
+                ),
+                SExpr::any(),
+                cxt
+            ))
+            .join(primitive!(
+                "#/expand",
+                "[,code]",
+                get!("code", cxt).expand(cxt)?,
+                SExpr::any(),
+                cxt
+            ))
             .join(primitive!(
                 "#/spread/make",
                 "[,spread-list]",
@@ -225,39 +709,37 @@ impl Bindings {
                             get!("spread-list", cxt)
                         ))
                 ),
-                Spread(
-                    cxt(ident!("spread-list"))
-                        .unwrap()
-                        .as_list()
-                        .unwrap()
-                        .iter()
-                        .map(|e| e.evals_to())
-                        .collect::<Vec<_>>()
-                ),
+                match cxt(ident!("spread-list"))
+                    .and_then(|code| code.as_literal())
+                    .map(|code| code.evals_to(cxt))
+                {
+                    Some(List(ptns)) => Spread(ptns),
+                    _ => Spread(vec![SExpr::many_any()]),
+                },
                 cxt
             ))
             .join(primitive!(
                 "#/list/head",
                 "[,head-list]",
-                if get!("head-list", cxt).as_list().unwrap().len() < 1 {
-                    List(vec![])
-                } else {
-                    get!("head-list", cxt).as_list().unwrap()[0].clone()
+                list(cxt, "head-list")?.into_iter().next().unwrap_or(List(vec![])),
+                match cxt(ident!("head-list")) {
+                    Some(List(ptns)) if ptns.iter().all(SExpr::matches_singular) => {
+                        ptns.into_iter().next().unwrap_or(List(vec![]))
+                    }
+                    _ => const_fold(eval, &["head-list"], cxt),
                 },
-                //TODO
-                Place(ident!("#/noread")),
                 cxt
             ))
             .join(primitive!(
                 "#/list/tail",
                 "[,tail-list]",
-                if !get!("tail-list", cxt).as_list().unwrap().is_empty() {
-                    List(get!("tail-list", cxt).as_list().unwrap()[1..].to_vec())
-                } else {
-                    List(vec![])
+                List(list(cxt, "tail-list")?.into_iter().skip(1).collect()),
+                match cxt(ident!("tail-list")) {
+                    Some(List(ptns)) if ptns.iter().all(SExpr::matches_singular) => {
+                        List(ptns.into_iter().skip(1).collect())
+                    }
+                    _ => const_fold(eval, &["tail-list"], cxt),
                 },
-                //TODO
-                Place(ident!("#/noread")),
                 cxt
             ))
             .join(primitive!(
@@ -267,74 +749,199 @@ impl Bindings {
                     get!("sigil", cxt).as_sigil().unwrap(),
                     Box::new(get!("expr", cxt))
                 ),
-                //TODO
-                Place(ident!("#/noread")),
+                match cxt(ident!("sigil")).and_then(|s| s.as_literal()) {
+                    Some(Sigil(sigil)) => UnarySigilApp(
+                        sigil,
+                        Box::new(cxt(ident!("expr")).unwrap_or_else(SExpr::any)),
+                    ),
+                    _ => SExpr::any(),
+                },
                 cxt
             ))
             .join(primitive!(
                 "#/ident/from-components",
                 "[,components]",
                 Ident(IDENTS.intern(crate::Ident{
-                    names: get!("components", cxt)
-                        .as_list()
-                        .unwrap()
-                        .iter()
-                        .map(|e| e.clone().as_ident().unwrap().names[0].clone())
-                        .collect::<Vec<_>>(),
+                    names: list(cxt, "components")?
+                        .into_iter()
+                        .map(|e| match e {
+                            Ident(id) => Ok(id.names[0].clone()),
+                            e => Err(interpreter_err!(NotA, SExprKind::Ident, e)),
+                        })
+                        .collect::<Result<Vec<_>, _>>()?,
                     tl_ns: false
                 })),
-                //TODO
-                Place(ident!("#/noread")),
+                const_fold(eval, &["components"], cxt),
                 cxt
             ))
             .join(primitive!(
                 "#/ident/from-int",
                 "[,n]",
-                Ident(IDENTS.intern(crate::Ident{
-                    names: vec![usize::try_from(get!("n", cxt).as_number().unwrap()).unwrap().to_string()],
-                    tl_ns: false
-                })),
-                //TODO
-                Place(ident!("#/noread")),
+                {
+                    let n = numbers(cxt, &["n"])?.remove(0);
+                    let n = usize::try_from(n).map_err(|_| {
+                        interpreter_err!(CannotConvert, "Only naturals can be made into identifiers", get!("n", cxt))
+                    })?;
+                    Ident(IDENTS.intern(crate::Ident{
+                        names: vec![n.to_string()],
+                        tl_ns: false
+                    }))
+                },
+                const_fold(eval, &["n"], cxt),
                 cxt
             ))
             .join(primitive!(
                 "#/ptn/at-ptn-time/make",
                 "[,fun]",
                 AtPtnTime(Box::new(get!("fun", cxt))),
-                //TODO
-                Place(ident!("#/noread")),
+                const_fold(eval, &["fun"], cxt),
                 cxt
             ))
             .join(primitive!(
                 "#/ident/concat",
                 "[,a ,b]",
                 {
-                    let a = get!("a", cxt).as_ident().unwrap();
-                    let b = get!("b", cxt).as_ident().unwrap();
-                    assert!(a.names.len() == 1 && b.names.len() == 1 && !a.tl_ns && !a.tl_ns);
+                    let (a, b) = (ident(cxt, "a")?, ident(cxt, "b")?);
+                    if a.names.len() != 1 || b.names.len() != 1 || a.tl_ns || b.tl_ns {
+                        throw_interpreter_err!(
+                            CannotConvert,
+                            "Can only concatenate identifiers outside of namespaces",
+                            List(vec![Ident(a), Ident(b)])
+                        )
+                    }
                     Ident(IDENTS.intern(crate::Ident{
                         names: vec![a.names[0].clone() + &b.names[0].clone()],
                         tl_ns: false,
                     }))
                 },
-                //TODO
-                Place(ident!("#/noread")),
+                const_fold(eval, &["a", "b"], cxt),
+                cxt
+            ))
+            .join(primitive!(
+                "#/ident/fresh",
+                "[,base]",
+                Ident(
+                    get!("base", cxt)
+                        .as_ident()
+                        .ok_or(interpreter_err!(NotA, SExprKind::Ident, get!("base", cxt)))?
+                        .fresh()
+                ),
+                SExpr::any(),
                 cxt
             ))
             .join(primitive!(
                 "#/ptn/acc/make",
                 "[[,acc ,init] ,pats]",
                 PtnAcc{
-                    acc: get!("acc", cxt).as_fun().unwrap(),
+                    acc: fun(cxt, "acc")?,
                     init: Option::<Bindings>::from_sexpr(get!("init", cxt))?,
-                    pats: get!("pats", cxt).as_list().unwrap(),
+                    pats: list(cxt, "pats")?,
                 },
-                LitMatch(Box::new(PtnAcc{
-                    acc:                     cxt(ident!("acc")).unwrap().as_fun().unwrap(),
-                    init: Option::<Bindings>::from_sexpr(cxt(ident!("init")).unwrap()).unwrap(),
-                    pats: cxt(ident!("pats")).unwrap().as_list().unwrap()
-                })),
+                const_fold(eval, &["acc", "init", "pats"], cxt),
+                cxt
+            ))
+            .join(primitive!(
+                "#/ptn/acc/declare",
+                "[,acc ,kind]",
+                {
+                    crate::infer::declare_acc(
+                        &fun(cxt, "acc")?,
+                        crate::infer::AccKind::from_sexpr(get!("kind", cxt))?,
+                    );
+                    UnarySigilApp(':', Box::new(List(vec![])))
+                },
+                UnarySigilApp(':', Box::new(List(vec![]))),
+                cxt
+            ))
+            .join(primitive!(
+                "#/bindings/unify",
+                "[,left ,right]",
+                Bindings::from_sexpr(get!("left", cxt))?
+                    .unify(&Bindings::from_sexpr(get!("right", cxt))?)
+                    .into_sexpr(),
+                const_fold(eval, &["left", "right"], cxt),
+                cxt
+            ))
+            .join(primitive!(
+                "#/ptn/guard/make",
+                "[,ptn ,pred]",
+                Guard{
+                    ptn: Box::new(get!("ptn", cxt)),
+                    pred: crate::Fun::from_sexpr(get!("pred", cxt))?,
+                },
+                const_fold(eval, &["ptn", "pred"], cxt),
+                cxt
+            ))
+            .join(primitive!(
+                "#/ptn/range/make",
+                "[,lower ,upper]",
+                Range(Box::new(crate::number::Range::new(
+                    crate::number::Bound::from_sexpr(get!("lower", cxt))?,
+                    crate::number::Bound::from_sexpr(get!("upper", cxt))?,
+                ))),
+                const_fold(eval, &["lower", "upper"], cxt),
+                cxt
+            ))
+            .join(primitive!(
+                "#/ptn/record/make",
+                "[,fields ,rest]",
+                Record{
+                    fields: list(cxt, "fields")?
+                        .into_iter()
+                        .map(crate::RecordField::from_sexpr)
+                        .collect::<Result<_, _>>()?,
+                    rest: Option::<SExpr>::from_sexpr(get!("rest", cxt))?
+                        .map(Box::new),
+                },
+                const_fold(eval, &["fields", "rest"], cxt),
+                cxt
+            ))
+            .join(primitive!(
+                "#/ptn/capture/make",
+                "[,name ,ptn]",
+                match get!("name", cxt) {
+                    Place(name) => Capture {
+                        name,
+                        ptn: Box::new(get!("ptn", cxt)),
+                    },
+                    name => throw_interpreter_err!(NotA, crate::SExprKind::Place, name),
+                },
+                const_fold(eval, &["name", "ptn"], cxt),
+                cxt
+            ))
+            .join(primitive!(
+                "#/ptn/regex/make",
+                "[,source]",
+                crate::regex::compile(&String::from_sexpr(get!("source", cxt))?)?,
+                const_fold(eval, &["source"], cxt),
+                cxt
+            ))
+            .join(primitive!(
+                "#/ptn/somewhere/make",
+                "[,pat]",
+                Somewhere(Box::new(get!("pat", cxt))),
+                const_fold(eval, &["pat"], cxt),
+                cxt
+            ))
+            .join(primitive!(
+                "#/ptn/all-matches",
+                "[,pat ,expr]",
+                List(
+                    get!("pat", cxt)
+                        .all_matches(&get!("expr", cxt))?
+                        .into_iter()
+                        .map(IntoSExpr::into_sexpr)
+                        .collect()
+                ),
+                // Matching can call guards and other functions
+                SExpr::any(),
+                cxt
+            ))
+            .join(primitive!(
+                "#/ptn/not/make",
+                "[,pat]",
+                Not(Box::new(get!("pat", cxt))),
+                const_fold(eval, &["pat"], cxt),
                 cxt
             ))
             .join(primitive!(
@@ -344,36 +951,82 @@ impl Bindings {
                     print!("{:#?}", get!("it", cxt));
                     get!("it", cxt)
                 },
-                UnarySigilApp('`',Box::new(cxt(ident!("it")).unwrap())),
+                cxt(ident!("it")).unwrap_or_else(SExpr::any),
                 cxt
             ))
             .join(primitive!(
                 "#/kleene/make",
-                "[,start ,next]",
+                "[,start ,next ,mode]",
                 Kleene{
                     start: Box::new(get!("start", cxt)),
-                    next: get!("next", cxt).as_fun().unwrap()
+                    next: fun(cxt, "next")?,
+                    mode: crate::KleeneMode::from_sexpr(get!("mode", cxt))?
                 },
-                {
-                    dbg!();
-                    patter_std!("any").unwrap()
-                },// Is there a correct way to write this?
+                const_fold(eval, &["start", "next", "mode"], cxt),
                 cxt
             ))
             .join(primitive!(
                 "#/ptn/consec/make",
                 "[,pats]",
-                Consecutive(get!("pats", cxt).as_list().unwrap()),
-                LitMatch(Box::new(Consecutive(
-                    cxt(ident!("pats")).unwrap().as_list().unwrap()
-                ))),
+                Consecutive(list(cxt, "pats")?),
+                const_fold(eval, &["pats"], cxt),
                 cxt
             ))
             .join(primitive!(
                 "#/zero-width",
                 "[,inner]",
                 ZeroWidth(Box::new(get!("inner", cxt))),
-                LitMatch(Box::new(ZeroWidth(Box::new(cxt(ident!("inner")).unwrap())))),
+                const_fold(eval, &["inner"], cxt),
+                cxt
+            ))
+            .join(primitive!(
+                "#/evals-to",
+                "[,quoted]",
+                get!("quoted", cxt).evals_to_in(cxt),
+                Place(ident!("#/noread")),
+                cxt
+            ))
+            .join(primitive!(
+                "#/ptn/subset?",
+                "[,sub ,sup]",
+                get!("sub", cxt).subset_of(&get!("sup", cxt)).into_sexpr(),
+                const_fold(eval, &["sub", "sup"], cxt),
+                cxt
+            ))
+            .join(primitive!(
+                "#/ptn/disjoint?",
+                "[,left ,right]",
+                get!("left", cxt).disjoint_from(&get!("right", cxt)).into_sexpr(),
+                const_fold(eval, &["left", "right"], cxt),
+                cxt
+            ))
+            .join(primitive!(
+                "#/ptn/missing",
+                "[,arms ,domain]",
+                List(crate::exhaust::missing(
+                    &list(cxt, "arms")?,
+                    &get!("domain", cxt),
+                )),
+                const_fold(eval, &["arms", "domain"], cxt),
+                cxt
+            ))
+            .join(primitive!(
+                "#/ptn/redundant",
+                "[,arms]",
+                List(
+                    crate::exhaust::redundant(&list(cxt, "arms")?)
+                        .into_iter()
+                        .map(|i| Number(crate::Number::from(i as isize)))
+                        .collect()
+                ),
+                const_fold(eval, &["arms"], cxt),
+                cxt
+            ))
+            .join(primitive!(
+                "#/same?",
+                "[,a ,b]",
+                (get!("a", cxt) == get!("b", cxt)).into_sexpr(),
+                const_fold(eval, &["a", "b"], cxt),
                 cxt
             ))
     }
@@ -413,10 +1066,35 @@ impl Context {
         None
     }
 
+    /// The function of the macro `ident` is bound to, if it is bound to one
+    pub fn lookup_macro(
+        &self,
+        ident: Interned<'static, Ident>,
+    ) -> Option<crate::Fun> {
+        for cxti in self.contexts.iter().rev() {
+            if let Some(value) = cxti.bindings.0.get(&ident) {
+                return match value {
+                    SExpr::Macro(fun) => Some(fun.clone()),
+                    _ => None,
+                };
+            }
+        }
+        None
+    }
+
+    /// Whether a macro is expanding a call to it
+    pub fn in_macro(&self) -> bool {
+        self.contexts
+            .iter()
+            .any(|cxti| cxti.kind == ScopeKind::Macro)
+    }
+
     pub fn empty() -> Context {
         Context {
             contexts: vec![ContextInner {
                 bindings: Bindings::empty(),
+                kind: ScopeKind::Module,
+                id: 0,
             }],
         }
     }
@@ -425,35 +1103,119 @@ impl Context {
         Context {
             contexts: vec![ContextInner {
                 bindings: Bindings::basic(),
+                kind: ScopeKind::Module,
+                id: 0,
             }],
         }
     }
 
     pub fn new() -> Context {
-        dbg!();
         Context {
             contexts: vec![ContextInner {
                 bindings: Bindings::new(),
+                kind: ScopeKind::Module,
+                id: 0,
             }],
         }
     }
 
     pub fn add_bindings(&mut self, bindings: &Bindings) {
-        self.add_bindings_at_depth(bindings, 0);
+        let innermost = self.contexts.len() - 1;
+        self.contexts[innermost].bindings.insert(bindings.clone());
+    }
+
+    pub fn add_bindings_in(
+        &mut self,
+        bindings: &Bindings,
+        scope: usize,
+    ) -> Result<(), InterpreterError> {
+        let index = self.scope_index(scope)?;
+        self.contexts[index].bindings.insert(bindings.clone());
+        Ok(())
+    }
+
+    /// Where the scope a handle refers to is on the stack, if it hasn't been
+    /// popped
+    fn scope_index(&self, scope: usize) -> Result<usize, InterpreterError> {
+        self.contexts
+            .iter()
+            .rposition(|cxti| cxti.id == scope)
+            .ok_or(interpreter_err!(DeadScope, scope))
+    }
+
+    /// The innermost scope that wasn't pushed by a call to an intrinsic.
+    pub fn current_scope(&self) -> usize {
+        let index = self
+            .contexts
+            .iter()
+            .rposition(|cxti| cxti.kind != ScopeKind::Intrinsic)
+            .unwrap_or(0);
+        self.contexts[index].id
+    }
+
+    /// The scope the innermost (non-intrinsic) function was called from.
+    pub fn caller_scope(&self) -> usize {
+        let args = match self
+            .contexts
+            .iter()
+            .rposition(|cxti| cxti.kind == ScopeKind::Args)
+        {
+            Some(args) => args,
+            None => return self.module_scope(),
+        };
+        // Skip the args scope and the closure scope pushed before it
+        let closure = self.contexts[..args]
+            .iter()
+            .rposition(|cxti| cxti.kind == ScopeKind::Closure)
+            .unwrap_or(0);
+        let index = self.contexts[..closure]
+            .iter()
+            .rposition(|cxti| cxti.kind != ScopeKind::Intrinsic)
+            .unwrap_or(0);
+        self.contexts[index].id
+    }
+
+    pub fn module_scope(&self) -> usize {
+        self.contexts[0].id
+    }
+
+    /// Runs `f` with every scope above `scope` temporarily removed, so that
+    /// code is evaluated as if it were written in `scope`.
+    pub fn in_scope<T>(
+        &mut self,
+        scope: usize,
+        f: impl FnOnce(&mut Context) -> Result<T, InterpreterError>,
+    ) -> Result<T, InterpreterError> {
+        let index = self.scope_index(scope)?;
+        let above = self.contexts.split_off(index + 1);
+        let result = f(self);
+        self.contexts.truncate(index + 1);
+        self.contexts.extend(above);
+        result
     }
 
-    pub fn add_bindings_at_depth(&mut self, bindings: &Bindings, depth: usize) {
-        assert!(
-            self.contexts.len() >= 1 + depth,
-            format!("Tried to add bindings too deep ({})", depth)
-        );
-        let idx = self.contexts.len() - 1 - depth;
-        self.contexts[idx].bindings.insert(bindings.clone());
+    /// Expands and evaluates `expr` as if written in `scope`, with `bindings`
+    /// visible only for the duration of the evaluation.
+    pub fn eval_in_block(
+        &mut self,
+        scope: usize,
+        bindings: &Bindings,
+        expr: &SExpr,
+    ) -> Result<SExpr, InterpreterError> {
+        self.in_scope(scope, |cxt| {
+            cxt.push_scope(ScopeKind::Block);
+            cxt.add_bindings(bindings);
+            let result = expr.expand(cxt).and_then(|expr| expr.eval(cxt));
+            cxt.pop_scope();
+            result
+        })
     }
 
-    pub fn push_scope(&mut self) {
+    pub fn push_scope(&mut self, kind: ScopeKind) {
         self.contexts.push(ContextInner {
             bindings: Bindings::empty(),
+            kind,
+            id: SCOPES.fetch_add(1, atomic::Ordering::Relaxed) + 1,
         });
     }
 
@@ -469,7 +1231,7 @@ impl Context {
 
     pub fn collapse(&self) -> Bindings {
         let mut collapsed = Bindings::empty();
-        for ContextInner { bindings } in &self.contexts {
+        for ContextInner { bindings, .. } in &self.contexts {
             for (id, val) in &bindings.0 {
                 collapsed.0.insert(*id, val.clone());
             }
@@ -482,7 +1244,7 @@ impl Context {
         keep: Vec<Interned<'static, Ident>>,
     ) -> Bindings {
         let mut collapsed = Bindings::empty();
-        for ContextInner { bindings } in &self.contexts {
+        for ContextInner { bindings, .. } in &self.contexts {
             for (id, val) in &bindings.0 {
                 if keep.binary_search(&id).is_ok() {
                     collapsed.0.insert(*id, val.clone());
@@ -514,3 +1276,146 @@ impl IntoSExpr for Bindings {
         )
     }
 }
+
+/// The list bound to `arg`
+fn list(cxt: &Context, arg: &str) -> Result<Vec<SExpr>, InterpreterError> {
+    let value = get!(arg, cxt);
+    value
+        .clone()
+        .as_list()
+        .ok_or_else(|| interpreter_err!(NotA, SExprKind::List, value))
+}
+
+/// The identifier bound to `arg`
+fn ident(
+    cxt: &Context,
+    arg: &str,
+) -> Result<Interned<'static, Ident>, InterpreterError> {
+    let value = get!(arg, cxt);
+    value
+        .clone()
+        .as_ident()
+        .ok_or_else(|| interpreter_err!(NotA, SExprKind::Ident, value))
+}
+
+/// The function bound to `arg`
+fn fun(cxt: &Context, arg: &str) -> Result<crate::Fun, InterpreterError> {
+    let value = get!(arg, cxt);
+    value
+        .clone()
+        .as_fun()
+        .ok_or_else(|| interpreter_err!(NotA, SExprKind::Fun, value))
+}
+
+/// The numbers bound to `args`, for the arithmetic and comparison
+/// intrinsics
+fn numbers(
+    cxt: &Context,
+    args: &[&str],
+) -> Result<Vec<Number>, InterpreterError> {
+    args.iter()
+        .map(|arg| {
+            let value = get!(arg, cxt);
+            value
+                .clone()
+                .as_number()
+                .ok_or_else(|| interpreter_err!(NotA, SExprKind::Number, value))
+        })
+        .collect()
+}
+
+/// Applies an arithmetic operation to the numbers bound to `args`, failing
+/// with `msg` where it isn't defined. Where it is defined on the exact values
+/// of floats, it failed for the result not fitting their precision.
+fn arithmetic(
+    cxt: &Context,
+    args: &[&str],
+    op: impl Fn(&[Number]) -> Option<Number>,
+    msg: &'static str,
+) -> Result<SExpr, InterpreterError> {
+    let numbers = numbers(cxt, args)?;
+    op(&numbers).map(SExpr::Number).ok_or_else(|| {
+        let exact = numbers
+            .iter()
+            .map(Number::to_exact)
+            .collect::<Option<Vec<_>>>();
+        let msg = if numbers.iter().any(|n| n.precision.float_base().is_some())
+            && exact.is_some_and(|exact| op(&exact).is_some())
+        {
+            "The result is out of the range of the float precision"
+        } else {
+            msg
+        };
+        interpreter_err!(
+            CannotCompute,
+            msg,
+            SExpr::List(numbers.into_iter().map(SExpr::Number).collect())
+        )
+    })
+}
+
+/// Like `arithmetic` on `lhs` and `rhs`, for the float intrinsics that round
+/// in the direction bound to `rounding`. There is no one float precision to
+/// round floats of different bases to, so they fail.
+fn rounded_arithmetic(
+    cxt: &Context,
+    op: impl Fn(&Number, &Number, Rounding) -> Option<Number>,
+    msg: &'static str,
+) -> Result<SExpr, InterpreterError> {
+    let rounding = Rounding::from_sexpr(get!("rounding", cxt))?;
+    let numbers = numbers(cxt, &["lhs", "rhs"])?;
+    if let (Some(left), Some(right)) = (
+        numbers[0].precision.float_base(),
+        numbers[1].precision.float_base(),
+    ) {
+        if left != right {
+            throw_interpreter_err!(
+                CannotCompute,
+                "Can only round floats of the same base",
+                SExpr::List(numbers.into_iter().map(SExpr::Number).collect())
+            )
+        }
+    }
+    arithmetic(cxt, &["lhs", "rhs"], |ns| op(&ns[0], &ns[1], rounding), msg)
+}
+
+/// The float precision of the base and the ranges of significands and
+/// exponents bound in `cxt`
+fn float_precision(cxt: &Context) -> Result<Precision, InterpreterError> {
+    let base = get!("base", cxt);
+    let range = |name| match get!(name, cxt) {
+        SExpr::Range(range) => Ok(range),
+        other => Err(interpreter_err!(NotA, SExprKind::Range, other)),
+    };
+    let (significands, exponents) =
+        (range("significands")?, range("exponents")?);
+    base.clone()
+        .as_number()
+        .and_then(|base| base.to_rational())
+        .filter(|base| base.is_integer())
+        .and_then(|base| base.to_integer().to_biguint())
+        .and_then(|base| Precision::float(base, &significands, &exponents))
+        .ok_or_else(|| {
+            interpreter_err!(
+                CannotConvert,
+                "Floats need an integer base of at least two and integer exponents",
+                SExpr::List(vec![
+                    base,
+                    SExpr::Range(significands),
+                    SExpr::Range(exponents)
+                ])
+            )
+        })
+}
+
+/// How the numbers bound to `lhs` and `rhs` compare
+fn compare(cxt: &Context) -> Result<Ordering, InterpreterError> {
+    let numbers = numbers(cxt, &["lhs", "rhs"])?;
+    numbers[0].partial_cmp(&numbers[1]).ok_or_else(|| {
+        interpreter_err!(
+            CannotCompute,
+            "Can only compare numbers with exact values",
+            SExpr::List(numbers.into_iter().map(SExpr::Number).collect())
+        )
+    })
+}
@@ -17,7 +17,10 @@ pub enum InterpreterErrorInfo {
     CannotCall(SExpr),
     NonMatchingArgs(Fun, Vec<SExpr>),
     CannotConvert(&'static str, SExpr),
+    CannotCompute(&'static str, SExpr),
     NotA(SExprKind, SExpr),
+    NoMatchingArm(SExpr, Vec<SExpr>),
+    DeadScope(usize),
     ReachedTheUnreachable,
 }
 
@@ -48,7 +51,19 @@ impl fmt::Display for InterpreterErrorInfo {
             CannotConvert(msg, example) => {
                 write!(f, "Cannot convert ({}) {:?}", msg, example)
             }
+            CannotCompute(msg, args) => {
+                write!(f, "Cannot compute ({}) {:?}", msg, args)
+            }
             NotA(kind, expr) => write!(f, "Not a {:?}: {:?}", kind, expr),
+            NoMatchingArm(expr, ptns) => write!(
+                f,
+                "No arm matched {:?}, tried {:?}",
+                expr,
+                SExpr::List(ptns.to_vec())
+            ),
+            DeadScope(scope) => {
+                write!(f, "Scope {} has already ended", scope)
+            }
             ReachedTheUnreachable => write!(f, "Reached the unreachable"),
         }
     }
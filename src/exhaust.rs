@@ -0,0 +1,147 @@
+//! Finding the values that no arm of a match covers, and the arms that can
+//! never be reached, by asking whether a pattern is useful after the arms
+//! before it.
+
+use crate::subsume::{useful, Truth};
+use crate::SExpr;
+
+/// How many times a pattern for missing values is split into finer ones
+const MAX_SPLITS: usize = 8;
+
+/// The indices of the arms that only match values earlier arms already do
+pub fn redundant(arms: &[SExpr]) -> Vec<usize> {
+    (0..arms.len())
+        .filter(|&i| {
+            useful(rows(&arms[..i]), vec![arms[i].clone()]) == Truth::No
+        })
+        .collect()
+}
+
+/// Patterns for the values matched by `domain` that no arm matches, as
+/// precisely as they can be found. Empty if the arms cover the domain, or if
+/// that can't be told.
+pub fn missing(arms: &[SExpr], domain: &SExpr) -> Vec<SExpr> {
+    uncovered(arms, domain, MAX_SPLITS)
+}
+
+fn rows(arms: &[SExpr]) -> Vec<Vec<SExpr>> {
+    arms.iter().map(|arm| vec![arm.clone()]).collect()
+}
+
+fn uncovered(arms: &[SExpr], domain: &SExpr, splits: usize) -> Vec<SExpr> {
+    if useful(rows(arms), vec![domain.clone()]) != Truth::Yes {
+        return vec![];
+    }
+    let parts = match splits {
+        0 => None,
+        _ => split(domain, arms),
+    };
+    let found = parts
+        .into_iter()
+        .flatten()
+        .flat_map(|part| uncovered(arms, &part, splits - 1))
+        .collect::<Vec<_>>();
+    if found.is_empty() {
+        vec![domain.clone()]
+    } else {
+        found
+    }
+}
+
+/// Splits a pattern into finer ones matching the same values between them,
+/// along the lines the arms draw
+fn split(domain: &SExpr, arms: &[SExpr]) -> Option<Vec<SExpr>> {
+    use SExpr::*;
+    if let Some(alternatives) = domain.union_alternatives() {
+        return Some(alternatives.to_vec());
+    }
+    let heads = arms
+        .iter()
+        .flat_map(|arm| match arm.union_alternatives() {
+            Some(alternatives) => alternatives.to_vec(),
+            None => vec![arm.clone()],
+        })
+        .collect::<Vec<_>>();
+    match domain {
+        Place(_) => {
+            let mut constructors = Vec::new();
+            for head in &heads {
+                let constructor = match head {
+                    List(ptns) if ptns.iter().all(SExpr::matches_singular) => {
+                        List(vec![SExpr::any(); ptns.len()])
+                    }
+                    UnarySigilApp(sigil, _) => {
+                        UnarySigilApp(*sigil, Box::new(SExpr::any()))
+                    }
+                    Range(_) => head.clone(),
+                    head if head.as_literal().is_some() => head.clone(),
+                    _ => continue,
+                };
+                if !constructors.contains(&constructor) {
+                    constructors.push(constructor);
+                }
+            }
+            if constructors.is_empty() {
+                return None;
+            }
+            let others = Not(Box::new(SExpr::union(constructors.clone())));
+            constructors.push(others);
+            Some(constructors)
+        }
+        List(ptns) if ptns.iter().all(SExpr::matches_singular) => {
+            for (i, ptn) in ptns.iter().enumerate() {
+                let column = heads
+                    .iter()
+                    .filter_map(|head| match head {
+                        List(items) if items.len() == ptns.len() => {
+                            Some(items[i].clone())
+                        }
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>();
+                if let Some(parts) = split(ptn, &column) {
+                    return Some(
+                        parts
+                            .into_iter()
+                            .map(|part| {
+                                let mut ptns = ptns.clone();
+                                ptns[i] = part;
+                                List(ptns)
+                            })
+                            .collect(),
+                    );
+                }
+            }
+            None
+        }
+        UnarySigilApp(sigil, inner) => {
+            let column = heads
+                .iter()
+                .filter_map(|head| match head {
+                    UnarySigilApp(s, inner) if s == sigil => {
+                        Some((**inner).clone())
+                    }
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            Some(
+                split(inner, &column)?
+                    .into_iter()
+                    .map(|part| UnarySigilApp(*sigil, Box::new(part)))
+                    .collect(),
+            )
+        }
+        Range(range) => heads.iter().find_map(|head| {
+            let other = head.as_range()?;
+            (!range.subset_of(&other) && range.intersects(&other)).then(|| {
+                range
+                    .intersection(&other)
+                    .into_iter()
+                    .chain(range.minus(&other))
+                    .map(|part| Range(Box::new(part)))
+                    .collect()
+            })
+        }),
+        _ => None,
+    }
+}
@@ -0,0 +1,114 @@
+//! Macro expansion. Each element of a bracketed list, which includes each
+//! top level form of a program, is expanded just before it is evaluated, so a
+//! macro can be used by the forms after the one defining it. Quoted and
+//! quasiquoted code is left alone, as it might be data or a pattern rather
+//! than code. Where quoted code does turn out to be code, such as the body of
+//! a function or arm, it is expanded by whatever receives it.
+//!
+//! Macros are hygienic in that the places bound by the quasiquoted code they
+//! return are renamed to fresh identifiers, which the code put into it with
+//! `@` can't refer to. That is all that is renamed: the other names in that
+//! code are free, and are looked up where the expansion is evaluated, so a
+//! caller's binding of one is what the expansion refers to.
+
+use std::collections::HashMap;
+
+use crate::context::{Context, ScopeKind};
+use crate::error::InterpreterError;
+use crate::intern::Interned;
+use crate::{Fun, Ident, SExpr};
+
+impl SExpr {
+    /// This code with every call to a macro replaced by its expansion
+    pub fn expand(&self, cxt: &mut Context) -> Result<SExpr, InterpreterError> {
+        use SExpr::*;
+        Ok(match self {
+            List(ls) => {
+                if let Some(Ident(head)) = ls.first() {
+                    if let Some(mac) = cxt.lookup_macro(*head) {
+                        return mac
+                            .expand_call(ls[1..].to_vec(), cxt)?
+                            .expand(cxt);
+                    }
+                }
+                List(
+                    ls.iter()
+                        .map(|e| e.expand(cxt))
+                        .collect::<Result<_, _>>()?,
+                )
+            }
+            UnarySigilApp('`' | '|', _) => self.clone(),
+            UnarySigilApp(sigil, arg) => {
+                UnarySigilApp(*sigil, Box::new(arg.expand(cxt)?))
+            }
+            expr => expr.clone(),
+        })
+    }
+
+    /// This quasiquoted template with the places it binds itself renamed to
+    /// fresh identifiers, as are the references to them in it
+    pub fn hygienic(&self) -> SExpr {
+        let mut renames = HashMap::new();
+        for binder in self.template_binders(1) {
+            renames.entry(binder).or_insert_with(|| binder.fresh());
+        }
+        self.renamed(&renames, 1)
+    }
+
+    /// The places bound by a quasiquoted template outside its unquotes
+    fn template_binders(&self, depth: usize) -> Vec<Interned<'static, Ident>> {
+        use SExpr::*;
+        match self {
+            UnarySigilApp(',', place) => {
+                place.clone().as_ident().into_iter().collect()
+            }
+            UnarySigilApp('@', _) if depth == 1 => vec![],
+            UnarySigilApp('@', expr) => expr.template_binders(depth - 1),
+            UnarySigilApp('|', expr) => expr.template_binders(depth + 1),
+            UnarySigilApp(_, expr) => expr.template_binders(depth),
+            List(ls) => {
+                ls.iter().flat_map(|e| e.template_binders(depth)).collect()
+            }
+            _ => vec![],
+        }
+    }
+
+    fn renamed(
+        &self,
+        renames: &HashMap<Interned<'static, Ident>, Interned<'static, Ident>>,
+        depth: usize,
+    ) -> SExpr {
+        use SExpr::*;
+        let inside = |sigil, expr: &SExpr, depth| {
+            UnarySigilApp(sigil, Box::new(expr.renamed(renames, depth)))
+        };
+        match self {
+            Ident(id) => Ident(*renames.get(id).unwrap_or(id)),
+            // Keywords aren't references
+            UnarySigilApp(':', _) => self.clone(),
+            UnarySigilApp('@', _) if depth == 1 => self.clone(),
+            UnarySigilApp('@', expr) => inside('@', expr, depth - 1),
+            UnarySigilApp('|', expr) => inside('|', expr, depth + 1),
+            UnarySigilApp(sigil, expr) => inside(*sigil, expr, depth),
+            List(ls) => {
+                List(ls.iter().map(|e| e.renamed(renames, depth)).collect())
+            }
+            expr => expr.clone(),
+        }
+    }
+}
+
+impl Fun {
+    /// Calls this function as a macro, with the code of the arguments of a
+    /// call to it
+    pub fn expand_call(
+        &self,
+        args: Vec<SExpr>,
+        cxt: &mut Context,
+    ) -> Result<SExpr, InterpreterError> {
+        cxt.push_scope(ScopeKind::Macro);
+        let code = self.call(args, cxt);
+        cxt.pop_scope();
+        code
+    }
+}
@@ -0,0 +1,670 @@
+//! Working out, without running anything, which pattern the value of an
+//! expression is guaranteed to match.
+//!
+//! Throughout, an "env" maps names to patterns of their values rather than to
+//! the values themselves.
+
+use lazy_static::lazy_static;
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::iter;
+use std::sync::Mutex;
+
+use crate::context::{Bindings, Context};
+use crate::error::{InterpreterError, InterpreterErrorInfo};
+use crate::intern::Interned;
+use crate::parse;
+use crate::{
+    make_sigil_ident, FromSExpr, Fun, Ident, IntoSExpr, KleeneMode, SExpr,
+};
+
+/// How many calls deep inference follows before answering `any`
+const MAX_CALL_DEPTH: usize = 32;
+
+thread_local! {
+    /// The calls whose results are being inferred, innermost last. Named
+    /// calls are recorded so that recursion can be cut off at the first
+    /// repeat.
+    static INFERRING: RefCell<Vec<Option<Interned<'static, Ident>>>> =
+        const { RefCell::new(Vec::new()) };
+}
+
+/// The result of matching a pattern against a pattern describing a value
+#[derive(Clone, Debug)]
+pub enum StaticMatch {
+    /// Every value described matches, binding places to these patterns
+    Always(Bindings),
+    /// Some of the values described might match
+    Maybe(Bindings),
+    Never,
+}
+
+lazy_static! {
+    /// How each accumulator passed to `declare_acc` combines, by function id
+    static ref ACC_KINDS: Mutex<HashMap<usize, AccKind>> =
+        Mutex::new(HashMap::new());
+
+    static ref UNION_ACC: Fun = {
+        let acc = Fun::native_acc(|cxt| {
+            let (left, right) = acc_args(cxt)?;
+            Ok(match (left, right) {
+                (Some(left), Some(right)) => Some(left.join(&right)),
+                (left, right) => left.or(right),
+            }
+            .into_sexpr())
+        });
+        declare_acc(&acc, AccKind::Union);
+        acc
+    };
+}
+
+/// How a `PtnAcc` combines the results of its patterns
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AccKind {
+    Union,
+    Intersection,
+}
+
+impl FromSExpr for AccKind {
+    fn from_sexpr(expr: SExpr) -> Result<AccKind, InterpreterError> {
+        match &expr {
+            SExpr::UnarySigilApp(':', name) => match &**name {
+                SExpr::Ident(id) if *id == ident!("union") => {
+                    Ok(AccKind::Union)
+                }
+                SExpr::Ident(id) if *id == ident!("intersection") => {
+                    Ok(AccKind::Intersection)
+                }
+                _ => Err(interpreter_err!(
+                    CannotConvert,
+                    "Unknown accumulator kind",
+                    expr.clone()
+                )),
+            },
+            _ => Err(interpreter_err!(
+                CannotConvert,
+                "Accumulator kinds are keywords",
+                expr.clone()
+            )),
+        }
+    }
+}
+
+impl StaticMatch {
+    /// Every one of `matches` has to succeed, as for the elements of a list
+    fn all_of(matches: Vec<StaticMatch>) -> StaticMatch {
+        let mut always = true;
+        let mut bindings = Bindings::empty();
+        for m in matches {
+            let b = match m {
+                StaticMatch::Always(b) => b,
+                StaticMatch::Maybe(b) => {
+                    always = false;
+                    b
+                }
+                StaticMatch::Never => return StaticMatch::Never,
+            };
+            // A repeated place only matches equal values, which we can't
+            // tell apart statically
+            if b.iter().any(|(id, _)| {
+                bindings.get(*id).is_some() && *id != ident!("#/noread")
+            }) {
+                always = false;
+            }
+            bindings.insert(b)
+        }
+        if always {
+            StaticMatch::Always(bindings)
+        } else {
+            StaticMatch::Maybe(bindings)
+        }
+    }
+
+    /// The value is described by any one of `matches`, as for the
+    /// alternatives of a union
+    fn any_of(matches: Vec<StaticMatch>) -> StaticMatch {
+        let mut always = true;
+        let mut alternatives = Vec::new();
+        for m in matches {
+            match m {
+                StaticMatch::Always(b) => alternatives.push(b),
+                StaticMatch::Maybe(b) => {
+                    always = false;
+                    alternatives.push(b)
+                }
+                StaticMatch::Never => always = false,
+            }
+        }
+        if alternatives.is_empty() {
+            return StaticMatch::Never;
+        }
+        let mut bindings = Bindings::empty();
+        for (place, _) in alternatives.iter().flat_map(Bindings::iter) {
+            if bindings.get(*place).is_none() {
+                let ptns = alternatives
+                    .iter()
+                    .map(|b| b.get(*place).cloned().unwrap_or_else(SExpr::any))
+                    .collect();
+                bindings.insert(Bindings::of(*place, &SExpr::union(ptns)));
+            }
+        }
+        if always {
+            StaticMatch::Always(bindings)
+        } else {
+            StaticMatch::Maybe(bindings)
+        }
+    }
+}
+
+impl SExpr {
+    /// A pattern matched by every value this expression might evaluate to,
+    /// given `env`.
+    pub fn evals_to(
+        &self,
+        env: &dyn Fn(Interned<'static, Ident>) -> Option<SExpr>,
+    ) -> SExpr {
+        use SExpr::*;
+        let ptn = match self.simplify() {
+            List(ls) => match ls.split_first() {
+                None => SExpr::nothing(),
+                Some((head, args)) => {
+                    let name = head.clone().as_ident();
+                    let fun = head.evals_to(env);
+                    let args = args.iter().map(|a| a.evals_to(env)).collect();
+                    call_evals_to(name, &fun, List(args).simplify(), env)
+                }
+            },
+            UnarySigilApp(sigil, arg) => call_evals_to(
+                None,
+                &Sigil(sigil).evals_to(env),
+                List(vec![arg.literal_ptn()]),
+                env,
+            ),
+            Ident(id) => env(id).unwrap_or_else(SExpr::any),
+            Sigil(sigil) => {
+                env(make_sigil_ident(sigil)).unwrap_or_else(SExpr::any)
+            }
+            Operation { evals_to, .. } => evals_to(env),
+            e @ Number(_) | e @ Scope(_) => e,
+            _ => SExpr::nothing(),
+        };
+        ptn.simplify()
+    }
+
+    /// `evals_to`, looking names up in `cxt`
+    pub fn evals_to_in(&self, cxt: &Context) -> SExpr {
+        self.evals_to(&|id| cxt.lookup(id).map(|v| v.literal_ptn()))
+    }
+
+    /// A pattern matching exactly this value
+    pub fn literal_ptn(&self) -> SExpr {
+        use SExpr::*;
+        match self {
+            List(ls) => List(ls.iter().map(SExpr::literal_ptn).collect()),
+            UnarySigilApp(sigil, arg) => {
+                UnarySigilApp(*sigil, Box::new(arg.literal_ptn()))
+            }
+            Sigil(_) | Ident(_) | Number(_) | Scope(_) => self.clone(),
+            _ => LitMatch(Box::new(self.clone())),
+        }
+    }
+
+    /// The only value this pattern matches, if it is one made by
+    /// `literal_ptn`
+    pub fn as_literal(&self) -> Option<SExpr> {
+        use SExpr::*;
+        match self {
+            LitMatch(value) => Some((**value).clone()),
+            Sigil(_) | Ident(_) | Number(_) | Scope(_) => Some(self.clone()),
+            List(ls) => ls
+                .iter()
+                .map(SExpr::as_literal)
+                .collect::<Option<_>>()
+                .map(List),
+            UnarySigilApp(sigil, arg) => {
+                arg.as_literal().map(|a| UnarySigilApp(*sigil, Box::new(a)))
+            }
+            _ => None,
+        }
+    }
+
+    /// Matches anything
+    pub fn any() -> SExpr {
+        SExpr::Place(ident!("#/noread"))
+    }
+
+    /// Matches nothing
+    pub fn nothing() -> SExpr {
+        SExpr::union(vec![])
+    }
+
+    /// Any number of anything, as a list element
+    pub fn many_any() -> SExpr {
+        SExpr::Kleene {
+            start: Box::new(SExpr::List(vec![])),
+            next: Fun {
+                body: Box::new(SExpr::Operation {
+                    eval: |_| Ok(SExpr::any()),
+                    evals_to: |_| SExpr::LitMatch(Box::new(SExpr::any())),
+                }),
+                args_ptn: Box::new(SExpr::any()),
+                closure: Box::new(Bindings::empty()),
+                next_clause: None,
+                id: crate::Fun::fresh_id(),
+                extensions: crate::Extensions::default(),
+            },
+            mode: KleeneMode::Greedy,
+        }
+    }
+
+    /// A pattern matching whatever any of `ptns` does, with nested unions
+    /// flattened and duplicate literals dropped
+    pub fn union(ptns: Vec<SExpr>) -> SExpr {
+        let mut alternatives: Vec<SExpr> = Vec::new();
+        for ptn in ptns {
+            let inner = match ptn.union_alternatives() {
+                Some(inner) => inner.to_vec(),
+                None => vec![ptn],
+            };
+            for alt in inner {
+                if let SExpr::Place(_) = alt {
+                    return SExpr::any();
+                }
+                let seen = alt.as_literal().is_some_and(|lit| {
+                    alternatives
+                        .iter()
+                        .any(|a| a.as_literal() == Some(lit.clone()))
+                });
+                if !seen {
+                    alternatives.push(alt);
+                }
+            }
+        }
+        if alternatives.len() == 1 {
+            alternatives.pop().unwrap()
+        } else {
+            SExpr::PtnAcc {
+                acc: Fun::union_acc(),
+                init: None,
+                pats: alternatives,
+            }
+        }
+    }
+
+    /// The alternatives of a union pattern
+    pub fn union_alternatives(&self) -> Option<&[SExpr]> {
+        match self {
+            SExpr::PtnAcc { acc, init, pats }
+                if acc_kind(acc, init) == Some(AccKind::Union) =>
+            {
+                Some(pats)
+            }
+            _ => None,
+        }
+    }
+
+    /// The patterns of an intersection pattern
+    pub fn intersection_parts(&self) -> Option<&[SExpr]> {
+        match self {
+            SExpr::PtnAcc { acc, init, pats }
+                if acc_kind(acc, init) == Some(AccKind::Intersection) =>
+            {
+                Some(pats)
+            }
+            _ => None,
+        }
+    }
+
+    /// The places this pattern binds
+    pub fn places(&self) -> Vec<Interned<'static, Ident>> {
+        use SExpr::*;
+        if let Some(alternatives) = self.union_alternatives() {
+            // Only one alternative binds its places, so a place is bound as
+            // many times as the alternative binding it most does
+            let mut places: Vec<Interned<'static, crate::Ident>> = Vec::new();
+            for alternative in alternatives {
+                let mut extra = alternative.places();
+                for place in &places {
+                    if let Some(i) = extra.iter().position(|p| p == place) {
+                        extra.remove(i);
+                    }
+                }
+                places.extend(extra);
+            }
+            return places;
+        }
+        match self {
+            Place(id) => vec![*id],
+            List(ps) | Consecutive(ps) | PtnAcc { pats: ps, .. } => {
+                ps.iter().flat_map(SExpr::places).collect()
+            }
+            UnarySigilApp(_, p)
+            | ZeroWidth(p)
+            | Somewhere(p)
+            | Guard { ptn: p, .. } => p.places(),
+            Capture { name, ptn } => {
+                iter::once(*name).chain(ptn.places()).collect()
+            }
+            Record { fields, rest } => fields
+                .iter()
+                .map(|field| &field.ptn)
+                .chain(rest.as_deref())
+                .flat_map(SExpr::places)
+                .collect(),
+            Kleene { start, next, .. } => {
+                // Later repetitions usually bind the same places as the first
+                let start = start.clone().as_list().unwrap_or_default();
+                let first = match start.first() {
+                    Some(_) => None,
+                    None => next
+                        .call(vec![List(vec![])], &mut Context::empty())
+                        .ok(),
+                };
+                start.iter().chain(&first).flat_map(SExpr::places).collect()
+            }
+            _ => vec![],
+        }
+    }
+
+    /// Whether matching this pattern calls a guard's predicate, builds a
+    /// pattern at match time or asks a function for its repetitions, running
+    /// code that inference doesn't know about. Inference never matches these
+    /// patterns itself.
+    pub fn calls_unknown_functions(&self) -> bool {
+        use SExpr::*;
+        match self {
+            Guard { .. } | AtPtnTime(_) | Kleene { .. } => true,
+            List(ps) | Consecutive(ps) | PtnAcc { pats: ps, .. } => {
+                ps.iter().any(SExpr::calls_unknown_functions)
+            }
+            UnarySigilApp(_, p)
+            | Not(p)
+            | Somewhere(p)
+            | ZeroWidth(p)
+            | Capture { ptn: p, .. } => p.calls_unknown_functions(),
+            Record { fields, rest } => fields
+                .iter()
+                .map(|field| &field.ptn)
+                .chain(rest.as_deref())
+                .any(SExpr::calls_unknown_functions),
+            _ => false,
+        }
+    }
+
+    /// Whether this pattern binds some place more than once, only matching
+    /// where the values bound to it are equal
+    pub fn repeats_places(&self) -> bool {
+        let places = self.places();
+        places.iter().enumerate().any(|(i, place)| {
+            *place != ident!("#/noread") && places[..i].contains(place)
+        })
+    }
+
+    /// Matches this pattern against every value matched by `value`
+    pub fn bind_static(&self, value: &SExpr) -> StaticMatch {
+        use SExpr::*;
+        let literal = value
+            .as_literal()
+            .filter(|_| !self.calls_unknown_functions());
+        if let Some(value) = literal {
+            return match self.match_ptn(&value) {
+                Ok(Some(b)) => StaticMatch::Always(Bindings::of_contents(
+                    b.iter().map(|(id, v)| (*id, v.literal_ptn())).collect(),
+                )),
+                Ok(None) => StaticMatch::Never,
+                Err(_) => self.maybe_any(),
+            };
+        }
+        if let Some(alternatives) = value.union_alternatives() {
+            return StaticMatch::any_of(
+                alternatives.iter().map(|a| self.bind_static(a)).collect(),
+            );
+        }
+        match (self, value) {
+            (Place(id), value) => StaticMatch::Always(Bindings::of(*id, value)),
+            (Range(ptn), Range(value)) => {
+                if value.subset_of(ptn) {
+                    StaticMatch::Always(Bindings::empty())
+                } else if ptn.intersects(value) {
+                    StaticMatch::Maybe(Bindings::empty())
+                } else {
+                    StaticMatch::Never
+                }
+            }
+            (Guard { ptn, .. }, value) => match ptn.bind_static(value) {
+                StaticMatch::Always(b) | StaticMatch::Maybe(b) => {
+                    StaticMatch::Maybe(b)
+                }
+                StaticMatch::Never => StaticMatch::Never,
+            },
+            // The first match in pre-order is at the root if there is one
+            (Somewhere(ptn), value) => match ptn.bind_static(value) {
+                StaticMatch::Always(b) => StaticMatch::Always(b),
+                _ => self.maybe_any(),
+            },
+            (Not(ptn), value) => match ptn.bind_static(value) {
+                StaticMatch::Always(_) => StaticMatch::Never,
+                StaticMatch::Maybe(_) => StaticMatch::Maybe(Bindings::empty()),
+                StaticMatch::Never => StaticMatch::Always(Bindings::empty()),
+            },
+            (List(ps), List(vs))
+                if ps.iter().chain(vs).all(SExpr::matches_singular) =>
+            {
+                if ps.len() == vs.len() {
+                    StaticMatch::all_of(
+                        ps.iter()
+                            .zip(vs)
+                            .map(|(p, v)| p.bind_static(v))
+                            .collect(),
+                    )
+                } else {
+                    StaticMatch::Never
+                }
+            }
+            (UnarySigilApp(p_sig, p), UnarySigilApp(v_sig, v)) => {
+                if p_sig == v_sig {
+                    p.bind_static(v)
+                } else {
+                    StaticMatch::Never
+                }
+            }
+            (ptn, value) => match (ptn.shape(), value.shape()) {
+                (Some(p), Some(v)) if p != v => StaticMatch::Never,
+                _ => self.maybe_any(),
+            },
+        }
+    }
+
+    /// Might match, binding all places to `any`
+    fn maybe_any(&self) -> StaticMatch {
+        StaticMatch::Maybe(Bindings::of_contents(
+            self.places()
+                .into_iter()
+                .map(|p| (p, SExpr::any()))
+                .collect(),
+        ))
+    }
+
+    /// What every value matched by this pattern looks like on the outside
+    pub fn shape(&self) -> Option<Shape> {
+        use SExpr::*;
+        match self {
+            List(_) => Some(Shape::List),
+            Number(_) => Some(Shape::Number),
+            Ident(_) => Some(Shape::Ident),
+            Sigil(_) => Some(Shape::Sigil),
+            UnarySigilApp(sigil, _) => Some(Shape::UnarySigilApp(*sigil)),
+            ZeroWidth(_) => Some(Shape::ZeroWidth),
+            Scope(_) => Some(Shape::Scope),
+            Guard { ptn, .. } => ptn.shape(),
+            Record { .. } => Some(Shape::List),
+            Range(_) => Some(Shape::Number),
+            LitMatch(value) => match value.literal_ptn() {
+                LitMatch(_) => None,
+                ptn => ptn.shape(),
+            },
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Shape {
+    List,
+    Number,
+    Ident,
+    Sigil,
+    UnarySigilApp(char),
+    ZeroWidth,
+    Scope,
+}
+
+impl Fun {
+    /// A pattern matched by the result of calling this function with
+    /// arguments matched by `args`
+    pub fn evals_to(
+        &self,
+        args: &SExpr,
+        env: &dyn Fn(Interned<'static, Ident>) -> Option<SExpr>,
+    ) -> SExpr {
+        let mut results = Vec::new();
+        for fun in &self.all_clauses() {
+            let (bindings, always) = match fun.args_ptn.bind_static(args) {
+                StaticMatch::Never => continue,
+                StaticMatch::Maybe(b) => (b, false),
+                StaticMatch::Always(b) => (b, true),
+            };
+            let closure_env = |id| {
+                fun.closure
+                    .get(id)
+                    .map(SExpr::literal_ptn)
+                    .or_else(|| env(id))
+            };
+            results.push(
+                fun.body.evals_to(&with_bindings(&bindings, &closure_env)),
+            );
+            if always {
+                break;
+            }
+        }
+        SExpr::union(results)
+    }
+
+    /// The accumulator of union patterns, which match if any of their
+    /// patterns does. It is always the same function, which is declared to
+    /// `acc_kind` as a union.
+    pub fn union_acc() -> Fun {
+        UNION_ACC.clone()
+    }
+
+    fn native_acc(
+        eval: fn(&mut Context) -> Result<SExpr, InterpreterError>,
+    ) -> Fun {
+        Fun {
+            body: Box::new(SExpr::Operation {
+                eval,
+                evals_to: |_| SExpr::any(),
+            }),
+            args_ptn: Box::new(SExpr::List(vec![
+                SExpr::Place(ident!("acc-left")),
+                SExpr::Place(ident!("acc-right")),
+            ])),
+            closure: Box::new(Bindings::empty()),
+            next_clause: None,
+            id: crate::Fun::fresh_id(),
+            extensions: crate::Extensions::default(),
+        }
+    }
+}
+
+fn acc_args(
+    cxt: &mut Context,
+) -> Result<(Option<Bindings>, Option<Bindings>), InterpreterError> {
+    Ok((
+        Option::<Bindings>::from_sexpr(get!("acc-left", cxt))?,
+        Option::<Bindings>::from_sexpr(get!("acc-right", cxt))?,
+    ))
+}
+
+/// Records that `acc` combines like `kind`, for inference to rely on. Nothing
+/// checks this, so it is only for accumulators that are known to.
+pub fn declare_acc(acc: &Fun, kind: AccKind) {
+    ACC_KINDS.lock().unwrap().insert(acc.id, kind);
+}
+
+/// Whether `acc` combines like a union or an intersection. Only accumulators
+/// passed to `declare_acc` are known, since finding out otherwise would mean
+/// running them, and the patterns using the rest are left alone by inference.
+pub fn acc_kind(acc: &Fun, init: &Option<Bindings>) -> Option<AccKind> {
+    let kind = ACC_KINDS.lock().unwrap().get(&acc.id).copied();
+    match (init.is_some(), kind?) {
+        (false, AccKind::Union) => Some(AccKind::Union),
+        (true, AccKind::Intersection) => Some(AccKind::Intersection),
+        _ => None,
+    }
+}
+
+/// Extends `env` with `bindings`
+pub fn with_bindings<'a>(
+    bindings: &'a Bindings,
+    env: &'a dyn Fn(Interned<'static, Ident>) -> Option<SExpr>,
+) -> impl Fn(Interned<'static, Ident>) -> Option<SExpr> + 'a {
+    move |id| bindings.get(id).cloned().or_else(|| env(id))
+}
+
+/// The result of an intrinsic when the patterns of all the named `args` are
+/// literal, otherwise `any`. Only intrinsics that call no functions and need
+/// nothing from the environment are folded, so that an error means the call
+/// always fails.
+pub fn const_fold(
+    eval: fn(&mut Context) -> Result<SExpr, InterpreterError>,
+    args: &[&str],
+    env: &dyn Fn(Interned<'static, Ident>) -> Option<SExpr>,
+) -> SExpr {
+    let mut cxt = Context::basic();
+    for arg in args {
+        match env(ident!(arg)).and_then(|p| p.as_literal()) {
+            Some(value) => cxt.add_bindings(&Bindings::of(ident!(arg), &value)),
+            None => return SExpr::any(),
+        }
+    }
+    match eval(&mut cxt) {
+        Ok(value) => value.literal_ptn(),
+        Err(err)
+            if matches!(
+                err.info,
+                InterpreterErrorInfo::UnknownName(_)
+                    | InterpreterErrorInfo::DeadScope(_)
+            ) =>
+        {
+            SExpr::any()
+        }
+        Err(_) => SExpr::nothing(),
+    }
+}
+
+/// The pattern of the result of calling whatever `fun` matches
+fn call_evals_to(
+    name: Option<Interned<'static, Ident>>,
+    fun: &SExpr,
+    args: SExpr,
+    env: &dyn Fn(Interned<'static, Ident>) -> Option<SExpr>,
+) -> SExpr {
+    let fun = match fun.as_literal() {
+        Some(SExpr::Fun(fun)) => fun,
+        _ => return SExpr::any(),
+    };
+    let recursive = INFERRING.with(|inferring| {
+        let inferring = inferring.borrow();
+        inferring.len() >= MAX_CALL_DEPTH
+            || (name.is_some() && inferring.contains(&name))
+    });
+    if recursive {
+        return SExpr::any();
+    }
+    INFERRING.with(|inferring| inferring.borrow_mut().push(name));
+    let result = fun.evals_to(&args, env);
+    INFERRING.with(|inferring| inferring.borrow_mut().pop());
+    result
+}
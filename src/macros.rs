@@ -5,6 +5,7 @@ macro_rules! patter {
     }};
 }
 
+#[cfg(test)]
 macro_rules! patter_std {
     ($code:expr) => {
         patter!($code).eval(&mut crate::STD_CXT.clone())
@@ -28,33 +29,44 @@ macro_rules! get {
 
 macro_rules! primitive {
     ($name:expr, $ptn:expr, $impl:expr, $evals_to:expr, $cxt:ident) => {
-        &Bindings::of(
-            ident!($name),
-            &SExpr::Fun(crate::Fun {
-                body: Box::new(SExpr::Operation {
-                    eval: |#[allow(unused_mut)] mut $cxt: &mut Context| {
-                        #[allow(unused_imports)]
-                        use crate::SExpr::*;
-                        Ok($impl)
-                    },
-                    evals_to:
-                        |#[allow(unused_variables)] $cxt: &dyn Fn(
-                            Interned<'static, Ident>,
-                        )
-                            -> Option<
-                            SExpr,
-                        >| {
-                            #[allow(unused_imports)]
-                            use crate::SExpr::*;
-                            $evals_to
-                        },
+        &{
+            // A named item, so that `$evals_to` can constant fold by calling
+            // it
+            #[allow(unused_mut)]
+            fn eval(
+                mut $cxt: &mut Context,
+            ) -> Result<SExpr, crate::error::InterpreterError> {
+                #[allow(unused_imports)]
+                use crate::SExpr::*;
+                Ok($impl)
+            }
+            Bindings::of(
+                ident!($name),
+                &SExpr::Fun(crate::Fun {
+                    body: Box::new(SExpr::Operation {
+                        eval,
+                        evals_to:
+                            |#[allow(unused_variables)] $cxt: &dyn Fn(
+                                Interned<'static, Ident>,
+                            )
+                                -> Option<
+                                SExpr,
+                            >| {
+                                #[allow(unused_imports)]
+                                use crate::SExpr::*;
+                                $evals_to
+                            },
+                    }),
+                    args_ptn: Box::new(
+                        patter!($ptn).eval(&mut Context::basic()).unwrap(),
+                    ),
+                    closure: Box::new(Context::basic().collapse()),
+                    next_clause: None,
+                    id: crate::Fun::fresh_id(),
+                    extensions: crate::Extensions::default(),
                 }),
-                args_ptn: Box::new(
-                    patter!($ptn).eval(&mut Context::basic()).unwrap(),
-                ),
-                closure: Box::new(Context::basic().collapse()),
-            }),
-        )
+            )
+        }
     };
 }
 
@@ -6,23 +6,33 @@
 
 #[macro_use]
 mod macros;
+mod check;
 mod context;
 mod error;
+mod exhaust;
+mod expand;
+mod infer;
 mod intern;
+mod matcher;
 mod number;
 mod parse;
+mod regex;
+mod rewrite;
+mod subsume;
 
 use itertools::merge;
 use lazy_static::lazy_static;
-use num::BigInt;
+use num::{BigInt, ToPrimitive};
 use unicode_segmentation::UnicodeSegmentation;
 
 use std::fmt;
 use std::fmt::Debug;
 use std::fmt::Display;
 use std::iter;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 
-use crate::context::{Bindings, Context};
+use crate::context::{Bindings, Context, ScopeKind};
 use crate::error::InterpreterError;
 use crate::intern::{Interned, Interner};
 use crate::number::{Number, NumberRep, Precision};
@@ -31,6 +41,12 @@ lazy_static! {
     static ref IDENTS: Interner<Ident> = Interner::new();
 }
 
+/// How many fresh identifiers have been made
+static FRESH_IDENTS: AtomicUsize = AtomicUsize::new(0);
+
+/// How many functions have been made, so that each gets its own id
+static FUNS: AtomicUsize = AtomicUsize::new(0);
+
 lazy_static! {
     static ref STD_CXT: Context = {
         let mut cxt = Context::new();
@@ -49,6 +65,9 @@ pub enum SExpr {
     Place(Interned<'static, Ident>),
     Number(Number),
     Fun(Fun),
+    /// A function called with the code of its arguments, returning code to
+    /// expand and evaluate in place of the call
+    Macro(Fun),
     UnarySigilApp(char, Box<SExpr>),
     Operation {
         eval: fn(&mut Context) -> Result<SExpr, InterpreterError>,
@@ -65,10 +84,37 @@ pub enum SExpr {
     Kleene {
         start: Box<SExpr>,
         next: Fun,
+        mode: KleeneMode,
     },
     AtPtnTime(Box<SExpr>),
     LitMatch(Box<SExpr>),
+    /// Matches whatever its pattern doesn't, binding nothing
+    Not(Box<SExpr>),
+    /// Matches what `ptn` does when `pred`, called with the bindings from
+    /// `ptn` in scope, returns `:true`
+    Guard {
+        ptn: Box<SExpr>,
+        pred: Fun,
+    },
+    /// Matches the numbers in a range by value
+    Range(Box<number::Range<Number>>),
+    /// Matches a list of `[key value]` pairs by key, in any order. The pairs
+    /// no field takes are matched against `rest`, if there is one.
+    Record {
+        fields: Vec<RecordField>,
+        rest: Option<Box<SExpr>>,
+    },
+    /// Matches a value when `ptn` matches it or anything inside it, binding
+    /// what the first match in pre-order does
+    Somewhere(Box<SExpr>),
+    /// Matches what `ptn` does among the elements of a list, binding `name`
+    /// to a list of the elements it matched
+    Capture {
+        name: Interned<'static, Ident>,
+        ptn: Box<SExpr>,
+    },
     ZeroWidth(Box<SExpr>),
+    Scope(usize),
     Never,
 }
 
@@ -79,6 +125,7 @@ pub enum SExprKind {
     Ident,
     Place,
     Fun,
+    Macro,
     UnarySigilApp,
     Number,
     Operation,
@@ -88,9 +135,16 @@ pub enum SExprKind {
     AtPtnTime,
     PtnAcc,
     LitMatch,
+    Not,
+    Guard,
+    Range,
+    Record,
+    Somewhere,
     Consecutive,
     Kleene,
+    Capture,
     ZeroWidth,
+    Scope,
     Never,
 }
 
@@ -105,6 +159,60 @@ pub struct Fun {
     body: Box<SExpr>,
     args_ptn: Box<SExpr>,
     closure: Box<Bindings>,
+    /// Tried, in order, when the args don't match `args_ptn`
+    next_clause: Option<Box<Fun>>,
+    /// Copies of a function share its id, and so are equal
+    id: usize,
+    /// Clauses added to this function after it was made, tried after its
+    /// own. Copies of a function share them.
+    extensions: Extensions,
+}
+
+/// The clauses added to a function with `extend`. Calls take the list as it
+/// is when they start, so extending a function while it runs doesn't change
+/// the calls already made.
+#[derive(Clone, Default)]
+pub struct Extensions(Arc<Mutex<Arc<Vec<Fun>>>>);
+
+impl Extensions {
+    fn get(&self) -> Arc<Vec<Fun>> {
+        self.0.lock().unwrap().clone()
+    }
+
+    fn add(&self, clauses: Vec<Fun>) {
+        let mut extensions = self.0.lock().unwrap();
+        let mut extended = (**extensions).clone();
+        extended.extend(clauses);
+        *extensions = Arc::new(extended);
+    }
+}
+
+// An extension can refer to the function it extends, so the clauses aren't
+// printed
+impl Debug for Extensions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<{} extensions>", self.get().len())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct RecordField {
+    key: SExpr,
+    ptn: SExpr,
+    /// Matched against `ptn` when there is no pair with the key
+    default: Option<SExpr>,
+}
+
+/// How many repetitions a Kleene pattern prefers to take
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KleeneMode {
+    /// As many as possible, giving them back if the rest doesn't match
+    Greedy,
+    /// As few as possible, taking more if the rest doesn't match
+    Lazy,
+    /// As many as possible, never giving them back. Each repetition also
+    /// takes the longest match it can on its own.
+    Possessive,
 }
 
 impl SExpr {
@@ -119,18 +227,23 @@ impl SExpr {
                             SExpr::List(vec![])
                         )
                     }
-                    ls[0]
-                        .clone()
-                        .eval(cxt)?
-                        .as_fun()
-                        .ok_or(interpreter_err!(CannotCall, ls[0].clone()))?
-                        .call(
-                            ls[1..]
-                                .iter()
-                                .map(|e| e.eval(&mut cxt))
-                                .collect::<Result<_, _>>()?,
-                            cxt,
-                        )?
+                    match ls[0].clone().eval(cxt)? {
+                        // Code built while running isn't expanded beforehand
+                        Macro(mac) => mac
+                            .expand_call(ls[1..].to_vec(), cxt)?
+                            .expand(cxt)?
+                            .eval(cxt)?,
+                        head => head
+                            .as_fun()
+                            .ok_or(interpreter_err!(CannotCall, ls[0].clone()))?
+                            .call(
+                                ls[1..]
+                                    .iter()
+                                    .map(|e| e.eval(&mut cxt))
+                                    .collect::<Result<_, _>>()?,
+                                cxt,
+                            )?,
+                    }
                 }
                 UnarySigilApp(sigil, arg) => {
                     let fun = Sigil(sigil.clone()).eval(&mut cxt)?;
@@ -149,16 +262,23 @@ impl SExpr {
                 e @ Spread(_)
                 | e @ Consecutive(_)
                 | e @ Kleene { .. }
+                | e @ Capture { .. }
                 | e @ LitMatch(_)
+                | e @ Not(_)
+                | e @ Guard { .. }
+                | e @ Range(_)
+                | e @ Record { .. }
+                | e @ Somewhere(_)
                 | e @ Place(_)
                 | e @ PtnAcc { .. }
                 | e @ Fun(_)
+                | e @ Macro(_)
                 | e @ ZeroWidth(_)
                 | e @ AtPtnTime(_) => {
                     throw_interpreter_err!(CannotEvaluate, e);
                     unreachable!();
                 }
-                s @ Number(_) => s,
+                s @ Number(_) | s @ Scope(_) => s,
                 Never => {
                     panic!("Somehow reached beyond the unreachable");
                 }
@@ -174,6 +294,50 @@ impl SExpr {
         })
     }
 
+    /// This quasiquoted code with unquotes (`@expr`) replaced by the values
+    /// of their expressions, and splices (`&@expr`) by the elements of
+    /// theirs. `depth` is how many quasiquotes the code is inside, an unquote
+    /// only being evaluated when it isn't inside more than it undoes.
+    fn quasiquote(
+        &self,
+        depth: usize,
+        cxt: &mut Context,
+    ) -> Result<SExpr, InterpreterError> {
+        use SExpr::*;
+        let inside = |sigil, expr: &SExpr, depth, cxt: &mut Context| {
+            Ok(UnarySigilApp(sigil, Box::new(expr.quasiquote(depth, cxt)?)))
+        };
+        Ok(match self {
+            UnarySigilApp('@', expr) if depth == 1 => expr.eval(cxt)?,
+            UnarySigilApp('@', expr) => inside('@', expr, depth - 1, cxt)?,
+            UnarySigilApp('|', expr) => inside('|', expr, depth + 1, cxt)?,
+            UnarySigilApp(sigil, expr) => inside(*sigil, expr, depth, cxt)?,
+            List(ls) => {
+                let mut elements = Vec::new();
+                for element in ls {
+                    match (depth, element) {
+                        (1, UnarySigilApp('&', spliced)) => match &**spliced {
+                            UnarySigilApp('@', expr) => {
+                                let value = expr.eval(cxt)?;
+                                elements.extend(value.clone().as_list().ok_or(
+                                    interpreter_err!(
+                                        NotA,
+                                        SExprKind::List,
+                                        value
+                                    ),
+                                )?)
+                            }
+                            _ => elements.push(element.quasiquote(depth, cxt)?),
+                        },
+                        _ => elements.push(element.quasiquote(depth, cxt)?),
+                    }
+                }
+                List(elements)
+            }
+            expr => expr.clone(),
+        })
+    }
+
     fn match_ptn(
         &self,
         expr: &SExpr,
@@ -188,107 +352,7 @@ impl SExpr {
                         None
                     }
                 }
-                (List(left), List(right)) => match (&**left, &**right) {
-                    ([], []) => Some(Bindings::empty()),
-                    ([], _) => None,
-                    ([ref pat, ..], []) if pat.matches_singular() => None,
-                    (&[.., ref pat], &[.., ref expr])
-                        if pat.matches_singular() =>
-                    {
-                        match (
-                            pat.match_ptn(&expr)?,
-                            List(left[..(left.len() - 1)].to_vec()).match_ptn(
-                                &List(right[..(right.len() - 1)].to_vec()),
-                            )?,
-                        ) {
-                            (Some(left_binds), Some(right_binds)) => {
-                                Some(left_binds.join(&right_binds))
-                            }
-                            _ => None,
-                        }
-                    }
-                    (&[ref pat, ..], &[ref expr, ..])
-                        if pat.matches_singular() =>
-                    {
-                        match (
-                            pat.match_ptn(&expr)?,
-                            List(left[1..].to_vec())
-                                .match_ptn(&List(right[1..].to_vec()))?,
-                        ) {
-                            (Some(left_binds), Some(right_binds)) => {
-                                Some(left_binds.join(&right_binds))
-                            }
-                            _ => None,
-                        }
-                    }
-                    ([ZeroWidth(left), l_rest @ ..], [ZeroWidth(right), r_rest @ ..]) => {
-                        Bindings::intersect(
-                            left.match_ptn(right)?,
-                            List(l_rest.to_vec()).match_ptn(&List(r_rest.to_vec()))?
-                        )
-                    }
-                    ([ZeroWidth(_), l_rest @ ..], r_rest) => {
-                        List(l_rest.to_vec()).match_ptn(&List(r_rest.to_vec()))?
-                    }
-                    (left @ [Kleene{ start, next }, ..], exprs) => {
-                        println!("Matching against a kleene: {:?}", exprs);
-                        let mut out_binds = Some(Bindings::basic()); //dbg
-                        let mut pats = start.clone().as_list().unwrap();
-                        for i in (pats.len())..=exprs.len() {
-                            debug_assert!(pats.len() == i);
-                            println!("Pats: {:?}", pats);
-                            // this is wrong for kleenes of consecs or kleenes
-                            if let (Some(left), Some(right)) =  (
-                                List(pats.clone()).match_ptn(
-                                    &List(exprs[..i].to_vec())
-                                )?,
-                                List(left[1..].to_vec())
-                                    .match_ptn(&List(exprs[i..].to_vec()))?
-                            ) {
-                                out_binds = Some(left.join(&right));
-                            }
-                            pats.push(next.call(
-                                vec![List(pats.clone())],
-                                &mut Context::empty()
-                            )?);
-                        }
-                        out_binds
-                    }
-                    (left @ [Consecutive(pats),..], exprs) => {
-                        match (
-                            List(pats.to_vec()).match_ptn(
-                                &List(exprs[..pats.len()].to_vec())
-                            )?,
-                            List(left[1..].to_vec()).match_ptn(
-                                &List(exprs[pats.len()..].to_vec())
-                            )?,
-                        ){
-                            (Some(left), Some(right)) => Some(left.join(&right)),
-                            _ => None
-                        }
-                    }
-                    (left @ [PtnAcc { acc, init, pats }, ..], exprs) => {
-                        let mut bindings = init.clone();
-                        for pat in pats {
-                            bindings = Option::<Bindings>::from_sexpr(patter_sr!(
-                                acc,
-                                SExpr::List(vec![
-                                    bindings.into_sexpr(),
-                                    {
-                                        let mut ls = left.to_vec();
-                                        ls[0] = pat.clone();
-                                        List(ls)
-                                    }.match_ptn(&List(exprs.to_vec()))?.into_sexpr(),
-                                ])
-                            )?)?;
-                        }
-                        bindings
-                    }
-                    (pats, exprs) => panic!(
-                        "Failed to handle list pattern match:\nPattern: List{:#?}\nExpr: List{:#?}",
-                        pats, exprs
-                    ),
-                },
+                (List(left), List(right)) => matcher::match_list(left, right)?,
                 (List(_), _) => None,
                 (AtPtnTime(pat), thing) => pat
                     .clone()
@@ -297,6 +361,50 @@ impl SExpr {
                     .call(vec![], &mut Context::empty())?
                     .match_ptn(thing)?,
                 (Place(id), thing) => Some(Bindings::of(*id, thing)),
+                (LitMatch(lit), thing) => {
+                    if **lit == *thing {
+                        Some(Bindings::empty())
+                    } else {
+                        None
+                    }
+                }
+                (Not(pat), thing) => match pat.match_ptn(thing)? {
+                    Some(_) => None,
+                    None => Some(Bindings::empty()),
+                },
+                (Range(range), Number(n)) => {
+                    range.contains(n).then(Bindings::empty)
+                }
+                (Range(_), _) => None,
+                (Record { fields, rest }, List(pairs)) => {
+                    matcher::match_record(fields, rest.as_deref(), pairs)?
+                }
+                (Record { .. }, _) => None,
+                (Somewhere(pat), thing) => {
+                    let mut found = None;
+                    for subtree in thing.subtrees() {
+                        found = pat.match_ptn(subtree)?;
+                        if found.is_some() {
+                            break;
+                        }
+                    }
+                    found
+                }
+                (Guard { ptn, pred }, thing) => match ptn.match_ptn(thing)? {
+                    Some(bindings) => {
+                        let pred = crate::Fun {
+                            closure: Box::new(
+                                (*pred.closure).clone().join(&bindings),
+                            ),
+                            ..pred.clone()
+                        };
+                        let passed = bool::from_sexpr(
+                            pred.call(vec![], &mut Context::empty())?,
+                        )?;
+                        passed.then_some(bindings)
+                    }
+                    None => None,
+                },
                 (PtnAcc { acc, init, pats }, expr) => {
                     let mut bindings = init.clone();
                     for pat in pats {
@@ -317,7 +425,16 @@ impl SExpr {
                         None
                     }
                 }
-                (UnarySigilApp(_,_), _) => None,
+                (UnarySigilApp(_, _), _) => None,
+                // These match a run of elements, so only mean something
+                // inside a list pattern
+                (Capture { .. }, _)
+                | (Consecutive(_), _)
+                | (Kleene { .. }, _) => throw_interpreter_err!(
+                    CannotCompute,
+                    "This pattern only matches among the elements of a list",
+                    self.clone()
+                ),
                 (a, b) => panic!("Unhandled pattern match: {:?}, {:?}", a, b),
             }
         };
@@ -330,22 +447,59 @@ impl SExpr {
         })
     }
 
+    /// The bindings of every match of this pattern against `expr` or
+    /// anything inside it, in pre-order
+    fn all_matches(
+        &self,
+        expr: &SExpr,
+    ) -> Result<Vec<Bindings>, InterpreterError> {
+        let mut matches = Vec::new();
+        for subtree in expr.subtrees() {
+            matches.extend(self.match_ptn(subtree)?);
+        }
+        Ok(matches)
+    }
+
+    /// This expression and everything inside it, each before what's inside
+    /// it
+    fn subtrees(&self) -> Vec<&SExpr> {
+        use SExpr::*;
+        let mut subtrees = vec![self];
+        match self {
+            List(ls) => subtrees.extend(ls.iter().flat_map(SExpr::subtrees)),
+            UnarySigilApp(_, inner) | ZeroWidth(inner) => {
+                subtrees.extend(inner.subtrees())
+            }
+            _ => {}
+        }
+        subtrees
+    }
+
     fn matches_singular(&self) -> bool {
         use SExpr::*;
         match self {
             Sigil(_)
             | Ident(_)
             | LitMatch(_)
+            | Not(_)
+            | Guard { .. }
+            | Range(_)
+            | Record { .. }
+            | Somewhere(_)
             | List(_)
             | Place(_)
             | Fun(_)
+            | Macro(_)
             | UnarySigilApp(_, _)
             | Number(_)
+            | Scope(_)
             | Operation { .. } => true,
             PtnAcc { pats, .. } => pats.iter().all(|p| p.matches_singular()),
-            Consecutive(_) | Kleene { .. } | AtPtnTime(_) | ZeroWidth(_) => {
-                false
-            }
+            Consecutive(_)
+            | Kleene { .. }
+            | Capture { .. }
+            | AtPtnTime(_)
+            | ZeroWidth(_) => false,
             Spread(_) | Never => unreachable!(),
         }
     }
@@ -353,25 +507,30 @@ impl SExpr {
     fn matches_literally(&self) -> bool {
         use SExpr::*;
         match self {
-            Sigil(_) | Ident(_) | Number(_) | Operation { .. } => true,
+            Sigil(_) | Ident(_) | Number(_) | Scope(_) | Operation { .. } => {
+                true
+            }
             List(ls) => ls.iter().all(|e| e.matches_literally()),
             Place(_)
             | Fun(_)
+            | Macro(_)
             | UnarySigilApp(_, _)
             | PtnAcc { .. }
             | Consecutive(_)
             | Kleene { .. }
+            | Capture { .. }
             | AtPtnTime(_)
                 | ZeroWidth(_) //sortof
-            | LitMatch(_) => false,
+            | LitMatch(_)
+            | Not(_)
+            | Guard { .. }
+            | Range(_)
+            | Record { .. }
+            | Somewhere(_) => false,
             Spread(_) | Never => unreachable!(),
         }
     }
 
-    fn evals_to(&self) -> SExpr {
-        unimplemented!();
-    }
-
     fn simplify(&self) -> SExpr {
         use SExpr::*;
         match self {
@@ -407,14 +566,18 @@ impl SExpr {
             Fun(crate::Fun {
                 body: a,
                 args_ptn: b,
+                next_clause,
                 ..
-            }) => {
-                merge(a.referenced_idents_inner(), b.referenced_idents_inner())
-                    .collect::<Vec<_>>()
-            }
-            LitMatch(expr) | AtPtnTime(expr) | ZeroWidth(expr) => {
-                expr.referenced_idents_inner()
-            }
+            }) => merge(
+                merge(a.referenced_idents_inner(), b.referenced_idents_inner()),
+                next_clause
+                    .iter()
+                    .flat_map(|f| Fun((**f).clone()).referenced_idents_inner()),
+            )
+            .collect::<Vec<_>>(),
+            Macro(fun) => Fun(fun.clone()).referenced_idents_inner(),
+            LitMatch(expr) | Not(expr) | Somewhere(expr) | AtPtnTime(expr)
+            | ZeroWidth(expr) => expr.referenced_idents_inner(),
             UnarySigilApp(sig, arg) => merge(
                 iter::once(make_sigil_ident(*sig)),
                 arg.referenced_idents_inner(),
@@ -438,12 +601,31 @@ impl SExpr {
                 ),
             )
             .collect::<Vec<_>>(),
-            Kleene { start, next } => merge(
+            Kleene { start, next, .. } => merge(
                 start.referenced_idents_inner(),
                 Fun(next.clone()).referenced_idents_inner(),
             )
             .collect(),
-            Number(_) | Operation { .. } => vec![],
+            Record { fields, rest } => {
+                let mut parts = Vec::new();
+                for field in fields {
+                    parts.push(field.key.clone());
+                    parts.push(field.ptn.clone());
+                    parts.extend(field.default.clone());
+                }
+                parts.extend(rest.as_deref().cloned());
+                List(parts).referenced_idents_inner()
+            }
+            Guard { ptn, pred } => merge(
+                ptn.referenced_idents_inner(),
+                Fun(pred.clone()).referenced_idents_inner(),
+            )
+            .collect(),
+            Capture { name, ptn } => {
+                merge(iter::once(*name), ptn.referenced_idents_inner())
+                    .collect()
+            }
+            Number(_) | Scope(_) | Operation { .. } | Range(_) => vec![],
             Never => unreachable!(),
         }
     }
@@ -457,15 +639,23 @@ impl SExpr {
             SExpr::Spread(_) => Spread,
             SExpr::Place(_) => Place,
             SExpr::Fun(_) => Fun,
+            SExpr::Macro(_) => Macro,
             SExpr::UnarySigilApp(_, _) => UnarySigilApp,
             SExpr::Number(_) => Number,
             SExpr::Operation { .. } => Operation,
             SExpr::AtPtnTime(_) => AtPtnTime,
             SExpr::PtnAcc { .. } => PtnAcc,
             SExpr::LitMatch(_) => LitMatch,
+            SExpr::Not(_) => Not,
+            SExpr::Guard { .. } => Guard,
+            SExpr::Range(_) => Range,
+            SExpr::Record { .. } => Record,
+            SExpr::Somewhere(_) => Somewhere,
             SExpr::Consecutive(_) => Consecutive,
             SExpr::Kleene { .. } => Kleene,
+            SExpr::Capture { .. } => Capture,
             SExpr::ZeroWidth(_) => ZeroWidth,
+            SExpr::Scope(_) => Scope,
             SExpr::Never => Never,
         }
     }
@@ -510,6 +700,14 @@ impl SExpr {
         }
     }
 
+    fn as_scope(self) -> Option<usize> {
+        if let SExpr::Scope(scope) = self {
+            Some(scope)
+        } else {
+            None
+        }
+    }
+
     fn as_solidified(self) -> Option<SExpr> {
         if let SExpr::UnarySigilApp(':', thing) = self {
             Some(*thing)
@@ -520,25 +718,83 @@ impl SExpr {
 }
 
 impl Fun {
+    fn fresh_id() -> usize {
+        FUNS.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// Calls the first clause whose `args_ptn` matches `args`, trying the
+    /// clauses added to this function after its own
     fn call(
         &self,
         args: Vec<SExpr>,
         mut cxt: &mut Context,
     ) -> Result<SExpr, InterpreterError> {
-        if let Some(bindings) =
-            self.args_ptn.match_ptn(&SExpr::List(args.clone()))?
-        {
-            cxt.push_scope();
-            cxt.add_bindings(&self.closure);
-            cxt.push_scope();
-            cxt.add_bindings(&bindings);
-            let expr = self.body.eval(&mut cxt);
-            cxt.pop_scope();
-            cxt.pop_scope();
-            expr
-        } else {
-            throw_interpreter_err!(NonMatchingArgs, self.clone(), args)
+        let args_list = SExpr::List(args.clone());
+        let extensions = self.extensions.get();
+        let clauses =
+            iter::successors(Some(self), |fun| fun.next_clause.as_deref())
+                .chain(extensions.iter());
+        for fun in clauses {
+            if let Some(bindings) = fun.args_ptn.match_ptn(&args_list)? {
+                let (closure_kind, args_kind) = if fun.is_intrinsic() {
+                    (ScopeKind::Intrinsic, ScopeKind::Intrinsic)
+                } else {
+                    (ScopeKind::Closure, ScopeKind::Args)
+                };
+                cxt.push_scope(closure_kind);
+                cxt.add_bindings(&fun.closure);
+                cxt.push_scope(args_kind);
+                cxt.add_bindings(&bindings);
+                let expr = fun.body.eval(&mut cxt);
+                cxt.pop_scope();
+                cxt.pop_scope();
+                return expr;
+            }
         }
+        throw_interpreter_err!(NonMatchingArgs, self.clone(), args)
+    }
+
+    /// Each clause of this function, in the order they are tried, leaving
+    /// out those added to it later.
+    fn clauses(&self) -> Vec<Fun> {
+        let mut clauses = Vec::new();
+        let mut clause = Some(self);
+        while let Some(fun) = clause {
+            clauses.push(Fun {
+                body: fun.body.clone(),
+                args_ptn: fun.args_ptn.clone(),
+                closure: fun.closure.clone(),
+                next_clause: None,
+                id: fun.id,
+                extensions: Extensions::default(),
+            });
+            clause = fun.next_clause.as_deref();
+        }
+        clauses
+    }
+
+    /// Each clause of this function, including those added to it later
+    fn all_clauses(&self) -> Vec<Fun> {
+        let mut clauses = self.clauses();
+        clauses.extend(self.extensions.get().iter().cloned());
+        clauses
+    }
+
+    /// A new function trying `clauses` in order, which later extensions of
+    /// the functions they came from don't change
+    fn from_clauses(clauses: Vec<Fun>) -> Option<Fun> {
+        clauses.into_iter().rev().fold(None, |next, clause| {
+            Some(Fun {
+                next_clause: next.map(Box::new),
+                id: Fun::fresh_id(),
+                extensions: Extensions::default(),
+                ..clause
+            })
+        })
+    }
+
+    fn is_intrinsic(&self) -> bool {
+        matches!(*self.body, SExpr::Operation { .. })
     }
 }
 
@@ -572,22 +828,98 @@ impl PartialEq for SExpr {
             (List(v0), List(v1)) => v0 == v1,
             (Ident(id0), Ident(id1)) | (Place(id0), Place(id1)) => id0 == id1,
 
-            (Fun(_), Fun(_)) => false,
+            (Fun(f0), Fun(f1)) | (Macro(f0), Macro(f1)) => f0 == f1,
             (Number(i0), Number(i1)) => i0 == i1,
             (UnarySigilApp(sig1, expr1), UnarySigilApp(sig2, expr2)) => {
                 sig1 == sig2 && expr1 == expr2
             }
             (Sigil(s1), Sigil(s2)) => s1 == s2,
             (ZeroWidth(left), ZeroWidth(right)) => left == right,
+            (Scope(left), Scope(right)) => left == right,
+            (LitMatch(left), LitMatch(right))
+            | (Not(left), Not(right))
+            | (Somewhere(left), Somewhere(right)) => left == right,
+            (Range(left), Range(right)) => left == right,
+            (
+                Record {
+                    fields: left_fields,
+                    rest: left_rest,
+                },
+                Record {
+                    fields: right_fields,
+                    rest: right_rest,
+                },
+            ) => left_fields == right_fields && left_rest == right_rest,
+            (Consecutive(left), Consecutive(right))
+            | (Spread(left), Spread(right)) => left == right,
+            (AtPtnTime(left), AtPtnTime(right)) => left == right,
+            (
+                Capture {
+                    name: left_name,
+                    ptn: left_ptn,
+                },
+                Capture {
+                    name: right_name,
+                    ptn: right_ptn,
+                },
+            ) => left_name == right_name && left_ptn == right_ptn,
+            (
+                PtnAcc {
+                    acc: left_acc,
+                    init: left_init,
+                    pats: left_pats,
+                },
+                PtnAcc {
+                    acc: right_acc,
+                    init: right_init,
+                    pats: right_pats,
+                },
+            ) => {
+                left_acc == right_acc
+                    && left_init == right_init
+                    && left_pats == right_pats
+            }
+            (
+                Kleene {
+                    start: left_start,
+                    next: left_next,
+                    mode: left_mode,
+                },
+                Kleene {
+                    start: right_start,
+                    next: right_next,
+                    mode: right_mode,
+                },
+            ) => {
+                left_start == right_start
+                    && left_next == right_next
+                    && left_mode == right_mode
+            }
+            (
+                Guard {
+                    ptn: left_ptn,
+                    pred: left_pred,
+                },
+                Guard {
+                    ptn: right_ptn,
+                    pred: right_pred,
+                },
+            ) => left_ptn == right_ptn && left_pred == right_pred,
+            // Operations are only found as the bodies of functions, which
+            // are compared by id
+            (Operation { .. }, Operation { .. }) => false,
+            (Never, Never) => true,
             (a, b) if a.kind() != b.kind() => false,
             (a, b) => panic!("Unhandled equality case: ({:?}, {:?})", a, b),
         }
     }
 }
 
+/// Functions are equal when one is a copy of the other, so that they have the
+/// same clauses and the same extensions
 impl PartialEq for Fun {
-    fn eq(&self, _: &Fun) -> bool {
-        false
+    fn eq(&self, other: &Fun) -> bool {
+        self.id == other.id
     }
 }
 
@@ -615,6 +947,7 @@ impl Debug for SExpr {
             Ident(id) => write!(f, "Ident({:?})", id),
             Place(id) => write!(f, "Place({:?})", id),
             Fun(fun) => f.debug_tuple("Fun").field(fun).finish(),
+            Macro(fun) => f.debug_tuple("Macro").field(fun).finish(),
             Number(i) => write!(f, "Number({:?})", i),
             Operation { .. } => write!(f, "Operation"),
             Sigil(s) => write!(f, "Sigil({})", s),
@@ -625,21 +958,58 @@ impl Debug for SExpr {
                 .field("pats", pats)
                 .finish(),
             LitMatch(expr) => f.debug_tuple("LitMatch").field(expr).finish(),
+            Not(expr) => f.debug_tuple("Not").field(expr).finish(),
+            Somewhere(expr) => f.debug_tuple("Somewhere").field(expr).finish(),
+            Range(range) => f.debug_tuple("Range").field(range).finish(),
+            Record { fields, rest } => f
+                .debug_struct("Record")
+                .field("fields", fields)
+                .field("rest", rest)
+                .finish(),
+            Guard { ptn, pred } => f
+                .debug_struct("Guard")
+                .field("ptn", ptn)
+                .field("pred", pred)
+                .finish(),
             Consecutive(exprs) => {
                 write!(f, "Consecutive")?;
                 f.debug_list().entries(exprs.iter()).finish()
             }
-            Kleene { start, next } => f
+            Kleene { start, next, mode } => f
                 .debug_struct("Kleene")
                 .field("start", start)
                 .field("next", next)
+                .field("mode", mode)
+                .finish(),
+            Capture { name, ptn } => f
+                .debug_struct("Capture")
+                .field("name", name)
+                .field("ptn", ptn)
                 .finish(),
             ZeroWidth(expr) => f.debug_tuple("ZeroWidth").field(expr).finish(),
+            Scope(scope) => write!(f, "Scope({})", scope),
             Never => write!(f, "Never"),
         }
     }
 }
 
+impl Ident {
+    /// A copy of this identifier with a suffix that can't be written in
+    /// source, so that it is equal to no other identifier
+    pub fn fresh(&self) -> Interned<'static, Ident> {
+        let n = FRESH_IDENTS.fetch_add(1, Ordering::Relaxed);
+        let mut names = self.names.clone();
+        match names.last_mut() {
+            Some(last) => last.push_str(&format!("%{}", n)),
+            None => names.push(format!("%{}", n)),
+        }
+        IDENTS.intern(Ident {
+            names,
+            tl_ns: self.tl_ns,
+        })
+    }
+}
+
 impl Debug for Ident {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}", self)
@@ -661,46 +1031,84 @@ impl Display for Ident {
         Ok(())
     }
 }
-/*
-impl Display for Fun {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(
-            f,
-            "<FUN: BODY={} ARGS={} CLOSURE={}>",
-            self.body, self.args_ptn, self.closure
-        )
-    }
-}
-*/
-/*
 impl Display for SExpr {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         use SExpr::*;
+        let write_all = |f: &mut fmt::Formatter<'_>, exprs: &[SExpr]| {
+            for (i, expr) in exprs.iter().enumerate() {
+                if i != 0 {
+                    write!(f, " ")?;
+                }
+                write!(f, "{}", expr)?;
+            }
+            Ok(())
+        };
         match self {
-            Sigil(sig) => write!(f, "{}", sig)?,
+            Sigil(sig) => write!(f, "{}", sig),
             List(ls) => {
                 write!(f, "(")?;
-                for expr in ls {
-                    write!(f, " {}", expr)?;
+                write_all(f, ls)?;
+                write!(f, ")")
+            }
+            UnarySigilApp('[', arg) => match &**arg {
+                List(ls) => {
+                    write!(f, "[")?;
+                    write_all(f, ls)?;
+                    write!(f, "]")
                 }
-                write!(f, ")")?;
+                arg => write!(f, "[{}", arg),
+            },
+            UnarySigilApp(sig, arg) => write!(f, "{}{}", sig, arg),
+            Ident(id) => write!(f, "{}", id),
+            Place(id) if *id == ident!("#/noread") => write!(f, "any"),
+            Place(id) => write!(f, ",{}", id),
+            Number(n) => write!(f, "{}", n),
+            Fun(fun) => write!(f, "<fun {}>", fun.args_ptn),
+            Macro(fun) => write!(f, "<macro {}>", fun.args_ptn),
+            Operation { .. } => write!(f, "<operation>"),
+            PtnAcc { pats, .. } => {
+                write!(f, "<acc ")?;
+                write_all(f, pats)?;
+                write!(f, ">")
             }
-            Fun(fun) => write!(f, "{}", fun)?,
-            Ident(id) => write!(f, "{}", id)?,
-            AtPtnTime(expr) => write!(f, "<AT-PTN-TIME {}>", expr)?,
-            Place(id) => write!(f, "<PLACE {}>", id)?,
-            UnarySigilApp(sig, arg) => write!(f, "{}{}", sig, arg)?,
-            Int(i) => write!(f, "{}", i)?,
-            Operation { .. } => write!(f, "<OPERATION>")?,
-            Keyword(id) => write!(f, "<KEYWORD {}>", id)?,
-            Spread(ls) => write!(f, "<SPREAD {}>", List(ls.clone()))?,
-            Rest(expr) => write!(f, "<REST {}>", expr)?,
-
+            Consecutive(exprs) => {
+                write!(f, "<consec ")?;
+                write_all(f, exprs)?;
+                write!(f, ">")
+            }
+            Spread(exprs) => {
+                write!(f, "<spread ")?;
+                write_all(f, exprs)?;
+                write!(f, ">")
+            }
+            Kleene { start, .. } => write!(f, "<kleene {}>", start),
+            Capture { name, ptn } => write!(f, "<capture ,{} {}>", name, ptn),
+            AtPtnTime(expr) => write!(f, "<at-ptn-time {}>", expr),
+            LitMatch(expr) => write!(f, "<lit {}>", expr),
+            Not(expr) => write!(f, "<not {}>", expr),
+            Somewhere(expr) => write!(f, "<somewhere {}>", expr),
+            Guard { ptn, .. } => write!(f, "<where {}>", ptn),
+            Range(range) => write!(f, "<range {}>", range),
+            Record { fields, rest } => {
+                write!(f, "<record")?;
+                for field in fields {
+                    write!(f, " [{} {}", field.key, field.ptn)?;
+                    if let Some(default) = &field.default {
+                        write!(f, " {}", default)?;
+                    }
+                    write!(f, "]")?;
+                }
+                if let Some(rest) = rest {
+                    write!(f, " &{}", rest)?;
+                }
+                write!(f, ">")
+            }
+            ZeroWidth(expr) => write!(f, "<zero-width {}>", expr),
+            Scope(scope) => write!(f, "<scope {}>", scope),
+            Never => write!(f, "<never>"),
         }
-        Ok(())
     }
 }
- */
 
 trait FromSExpr: Sized {
     fn from_sexpr(_: SExpr) -> Result<Self, InterpreterError>;
@@ -790,7 +1198,7 @@ impl<T: IntoSExpr> IntoSExpr for Option<T> {
             ]),
             None => SExpr::List(vec![SExpr::UnarySigilApp(
                 ':',
-                Box::new(SExpr::Ident(ident!("some"))),
+                Box::new(SExpr::Ident(ident!("none"))),
             )]),
         }
     }
@@ -844,6 +1252,62 @@ impl<T: FromSExpr> FromSExpr for Option<T> {
     }
 }
 
+impl FromSExpr for SExpr {
+    fn from_sexpr(expr: SExpr) -> Result<SExpr, InterpreterError> {
+        Ok(expr)
+    }
+}
+
+impl FromSExpr for RecordField {
+    fn from_sexpr(expr: SExpr) -> Result<RecordField, InterpreterError> {
+        match expr.clone().as_list().as_deref() {
+            Some([key, ptn]) => Ok(RecordField {
+                key: key.clone(),
+                ptn: ptn.clone(),
+                default: None,
+            }),
+            Some([key, ptn, default]) => Ok(RecordField {
+                key: key.clone(),
+                ptn: ptn.clone(),
+                default: Some(default.clone()),
+            }),
+            _ => Err(interpreter_err!(
+                CannotConvert,
+                "Record fields are [key ptn] or [key ptn default]",
+                expr
+            )),
+        }
+    }
+}
+
+impl FromSExpr for KleeneMode {
+    fn from_sexpr(expr: SExpr) -> Result<KleeneMode, InterpreterError> {
+        match &expr {
+            SExpr::UnarySigilApp(':', name) => match &**name {
+                SExpr::Ident(id) if *id == ident!("greedy") => {
+                    Ok(KleeneMode::Greedy)
+                }
+                SExpr::Ident(id) if *id == ident!("lazy") => {
+                    Ok(KleeneMode::Lazy)
+                }
+                SExpr::Ident(id) if *id == ident!("possessive") => {
+                    Ok(KleeneMode::Possessive)
+                }
+                _ => Err(interpreter_err!(
+                    CannotConvert,
+                    "Unknown Kleene mode",
+                    expr.clone()
+                )),
+            },
+            _ => Err(interpreter_err!(
+                CannotConvert,
+                "Kleene modes are keywords",
+                expr.clone()
+            )),
+        }
+    }
+}
+
 impl IntoSExpr for String {
     fn into_sexpr(self) -> SExpr {
         SExpr::UnarySigilApp(
@@ -876,6 +1340,129 @@ impl IntoSExpr for String {
     }
 }
 
+impl FromSExpr for String {
+    fn from_sexpr(expr: SExpr) -> Result<String, InterpreterError> {
+        let graphemes = expr.clone().as_list().ok_or(interpreter_err!(
+            NotA,
+            SExprKind::List,
+            expr.clone()
+        ))?;
+        let mut string = String::new();
+        for grapheme in graphemes {
+            for part in grapheme.clone().as_list().unwrap_or_default() {
+                match part {
+                    SExpr::Number(Number {
+                        rep: NumberRep::ArbitraryInteger(c),
+                        ..
+                    }) => {
+                        string.push(c.to_u32().and_then(char::from_u32).ok_or(
+                            interpreter_err!(
+                                CannotConvert,
+                                "Not a code point",
+                                grapheme.clone()
+                            ),
+                        )?)
+                    }
+                    SExpr::ZeroWidth(_) => {}
+                    _ => throw_interpreter_err!(
+                        CannotConvert,
+                        "Strings are lists of graphemes",
+                        expr.clone()
+                    ),
+                }
+            }
+        }
+        Ok(string)
+    }
+}
+
+impl FromSExpr for number::Bound<Number> {
+    fn from_sexpr(
+        expr: SExpr,
+    ) -> Result<number::Bound<Number>, InterpreterError> {
+        let keyword = |name| {
+            SExpr::UnarySigilApp(':', Box::new(SExpr::Ident(ident!(name))))
+        };
+        match &expr {
+            e if *e == keyword("unbounded") => Ok(number::Bound::Unbounded),
+            SExpr::List(ls) => match ls.as_slice() {
+                [kind, SExpr::Number(n)] if *kind == keyword("inclusive") => {
+                    Ok(number::Bound::Inclusive(n.clone()))
+                }
+                [kind, SExpr::Number(n)] if *kind == keyword("exclusive") => {
+                    Ok(number::Bound::Exclusive(n.clone()))
+                }
+                _ => Err(interpreter_err!(
+                    CannotConvert,
+                    "Bounds are [:inclusive n], [:exclusive n] or :unbounded",
+                    expr.clone()
+                )),
+            },
+            _ => Err(interpreter_err!(
+                CannotConvert,
+                "Bounds are [:inclusive n], [:exclusive n] or :unbounded",
+                expr.clone()
+            )),
+        }
+    }
+}
+
+impl FromSExpr for number::Rounding {
+    fn from_sexpr(expr: SExpr) -> Result<number::Rounding, InterpreterError> {
+        use number::Rounding::*;
+        let keyword = |name| {
+            SExpr::UnarySigilApp(':', Box::new(SExpr::Ident(ident!(name))))
+        };
+        [
+            ("nearest-even", NearestEven),
+            ("toward-zero", TowardZero),
+            ("up", Up),
+            ("down", Down),
+        ]
+        .iter()
+        .find(|(name, _)| expr == keyword(name))
+        .map(|(_, rounding)| *rounding)
+        .ok_or_else(|| {
+            interpreter_err!(
+                CannotConvert,
+                "Roundings are :nearest-even, :toward-zero, :up or :down",
+                expr
+            )
+        })
+    }
+}
+
+impl IntoSExpr for bool {
+    fn into_sexpr(self) -> SExpr {
+        let name = if self { "true" } else { "false" };
+        SExpr::UnarySigilApp(':', Box::new(SExpr::Ident(ident!(name))))
+    }
+}
+
+impl IntoSExpr for std::cmp::Ordering {
+    fn into_sexpr(self) -> SExpr {
+        use std::cmp::Ordering::*;
+        let name = match self {
+            Less => "less",
+            Equal => "equal",
+            Greater => "greater",
+        };
+        SExpr::UnarySigilApp(':', Box::new(SExpr::Ident(ident!(name))))
+    }
+}
+
+impl FromSExpr for bool {
+    fn from_sexpr(expr: SExpr) -> Result<bool, InterpreterError> {
+        if expr == true.into_sexpr() {
+            Ok(true)
+        } else if expr == false.into_sexpr() {
+            Ok(false)
+        } else {
+            Err(interpreter_err!(CannotConvert, "Not :true or :false", expr))
+        }
+    }
+}
+
 impl IntoSExpr for char {
     fn into_sexpr(self) -> SExpr {
         SExpr::Number(Number {
@@ -886,6 +1473,11 @@ impl IntoSExpr for char {
 }
 
 fn main() -> Result<(), InterpreterError> {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    if let Some("check") = args.first().map(String::as_str) {
+        std::process::exit(check::main(&args[1..]));
+    }
+
     dbg!(std::mem::size_of::<parse::Token>());
 
     let toks = dbg!(parse::lex(r#"(id "a")"#));
@@ -961,6 +1553,15 @@ mod tests {
         }))
     }
 
+    #[test]
+    fn lex_sigil_after_ident() {
+        use parse::Token::*;
+        assert_eq!(
+            parse::lex("a+b"),
+            vec![Word("a".to_string()), Sigil('+'), Word("b".to_string())]
+        );
+    }
+
     eval_test! {quote, "`(1 (#/add 2 3))", List(vec![
         number!(1),
         List(vec![
@@ -1072,6 +1673,59 @@ mod tests {
         "(with? [(consec :foo :bar)] [:foo :bar] `unit `never)",
         patter_std!("unit").unwrap()
     }
+    eval_test_std! {
+        consec_short_list,
+        "(with? [(consec :foo :bar)] [:foo] `:yes `:no)",
+        patter_std!(":no").unwrap()
+    }
+    eval_test_std! {
+        kleene_no_match,
+        "(with? [(many 1) :foo] [1 1 2 :foo] `:yes `:no)",
+        patter_std!(":no").unwrap()
+    }
+    eval_test_std! {
+        kleene_split_binds,
+        "(with? [(many any) ,a :foo (many any)] [1 2 :foo 3 :foo 4] `a `never)",
+        number!(3)
+    }
+    eval_test_std! {
+        kleene_lazy_binds,
+        "(with? [(many? any) ,a :foo (many any)] [1 2 :foo 3 :foo 4] `a `never)",
+        number!(2)
+    }
+    eval_test_std! {
+        kleene_collects,
+        "(with? [(many [,k ,v]) :end] [[:a 1] [:b 2] :end] `[k v] `never)",
+        patter!("((:a :b) (1 2))")
+    }
+    eval_test_std! {
+        kleene_collects_nothing,
+        "(with? [(many ,x)] [] `x `never)",
+        patter!("()")
+    }
+    eval_test_std! {
+        kleene_collects_consec,
+        "(with? [(many (consec ,k ,v))] [:a 1 :b 2] `v `never)",
+        patter!("(1 2)")
+    }
+    eval_test_std! {
+        kleene_collects_nested,
+        "(with? [(many [(many ,x)])] [[1 2] [] [3]] `x `never)",
+        patter!("((1 2) () (3))")
+    }
+    eval_test_std! {
+        kleene_one_or_more,
+        "[(with? [(many1 1)] [] `:yes `:no) (with? [(many1 1)] [1 1] `:yes `:no)]",
+        patter!("(:no :yes)")
+    }
+    eval_test_std! {
+        kleene_possessive,
+        "[
+            (with? [(many/possessive any) 1] [1 1] `:yes `:no)
+            (with? [(many/possessive 1) 2] [1 1 2] `:yes `:no)
+        ]",
+        patter!("(:no :yes)")
+    }
     eval_test_std! {
         bind,
         "(with? [(bind `foo 3)] [] `foo `never)",
@@ -1129,14 +1783,964 @@ mod tests {
         patter_std!("[[97 (vow `extended-grapheme-cluster)]]").unwrap()
     }
 
-    #[test]
-    fn never_panics() {
-        assert_eq!(
-            patter_std!("never").err().unwrap().info,
-            crate::error::InterpreterErrorInfo::ReachedTheUnreachable
-        );
+    eval_test_std! {
+        def_in_function,
+        "(list/tail ((\\ [] `[(def ,foo 5) foo])))",
+        List(vec![number!(5)])
     }
-
+    eval_test_std! {
+        with_binds_over_intrinsic_args,
+        "(with? ,alt 5 `alt `never)",
+        number!(5)
+    }
+    eval_test! {
+        scope_module,
+        "(#/scope/module)",
+        Scope(0)
+    }
+
+    #[test]
+    fn scope_dead() {
+        assert!(matches!(
+            patter_std!(
+                "[(def ,f (\\ [] `(#/scope/current)))
+                  (def ,s (f))
+                  (#/with? ,q 1 `q `:no s)]"
+            )
+            .err()
+            .unwrap()
+            .info,
+            crate::error::InterpreterErrorInfo::DeadScope(_)
+        ));
+    }
+
+    eval_test_std! {
+        match_literal_arm,
+        "(match 1 [0 `:zero] [1 `:one] [any `:many])",
+        patter_std!(":one").unwrap()
+    }
+    eval_test_std! {
+        match_binds_arm,
+        "(match [1 2] [[,a] `a] [[,a ,b] `(#/add a b)])",
+        number!(3)
+    }
+    eval_test_std! {
+        let_sequential,
+        "(let `((,x 1) ([,y ,z] [(#/add x 1) 3])) `[x y z])",
+        patter!("(1 2 3)")
+    }
+
+    eval_test_std! {fib_clauses, "(fib 10)", number!(55)}
+    eval_test_std! {list_len, "(list/len [1 2 3])", number!(3)}
+    eval_test_std! {
+        extend_clauses,
+        "(def ,f (clauses (\\ [0] `:zero)))
+         (def ,f (clauses f (\\ [1] `:one)))
+         [(f 0) (f 1)]",
+        patter!("(:zero :one)")
+    }
+    eval_test_std! {
+        extend_seen_by_earlier_callers,
+        "(def ,f (clauses (\\ [0] `:zero)))
+         (def ,g (\\ [,n] `(f n)))
+         (extend f (\\ [1] `:one))
+         [(g 0) (g 1)]",
+        patter!("(:zero :one)")
+    }
+    eval_test_std! {
+        extend_seen_by_guards,
+        "(def ,kind (clauses (\\ [1] `:one)))
+         (extend kind (\\ [2] `:two))
+         (with? (where ,x `(same (kind x) :two)) 2 `:yes `:no)",
+        patter_std!(":yes").unwrap()
+    }
+    eval_test_std! {
+        extend_seen_by_recursion,
+        "(def ,len (clauses (\\ [[]] `0)))
+         (extend len (\\ [,it] `(#/add 1 (len (list/tail it)))))
+         (len [1 2 3])",
+        number!(3)
+    }
+
+    eval_test_std! {evals_to_fold, "(evals-to `(#/add 1 2))", number!(3)}
+    eval_test_std! {evals_to_clause, "(evals-to `(fib 1))", number!(1)}
+    eval_test_std! {
+        evals_to_recursion,
+        "(evals-to `(fib 5))",
+        Place(ident!("#/noread"))
+    }
+    eval_test_std! {
+        evals_to_guarded_matches,
+        "(evals-to `(all-matches (where ,x `(pos? x)) [1 2]))",
+        Place(ident!("#/noread"))
+    }
+    eval_test_std! {
+        evals_to_list,
+        "(evals-to `[1 (list/head [2 unknown])])",
+        List(vec![number!(1), number!(2)])
+    }
+
+    #[test]
+    fn evals_to_union_of_branches() {
+        let ptn = patter_std!("(evals-to `(with? 0 unknown `:zero `:other))")
+            .unwrap();
+        for (value, matches) in
+            &[(":zero", true), (":other", true), (":third", false)]
+        {
+            assert_eq!(
+                ptn.match_ptn(&patter_std!(value).unwrap())
+                    .unwrap()
+                    .is_some(),
+                *matches
+            );
+        }
+    }
+
+    fn check(program: &str, possible: bool) -> Vec<check::Diagnostic> {
+        check::check_program(
+            &patter!(program).as_list().unwrap(),
+            &STD_CXT,
+            possible,
+        )
+    }
+
+    #[test]
+    fn check_extended() {
+        let diagnostics = check(
+            "((def ,f (clauses (\\ [0] `:zero)))
+              (extend f (\\ [1] `:one))
+              (f 1)
+              (f 2))",
+            false,
+        );
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].call, patter!("(f 2)"));
+    }
+
+    #[test]
+    fn check_arity() {
+        let diagnostics = check("((fib 1 2) (fib 3))", false);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].severity, check::Severity::Definite);
+        assert_eq!(diagnostics[0].call, patter!("(fib 1 2)"));
+    }
+
+    #[test]
+    fn check_quoted_body() {
+        let diagnostics =
+            check("((def ,f (\\ [,n] `(list/head n n))) (f 1))", false);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].call, patter!("(list/head n n)"));
+    }
+
+    #[test]
+    fn check_failing_intrinsics() {
+        for call in &[
+            "(#/ident/from-int -3)",
+            "(#/list/head 5)",
+            "(#/ident/concat 1 `a)",
+            "(#/ptn/consec/make :a)",
+        ] {
+            let diagnostics = check(&format!("((def ,x {}))", call), false);
+            assert_eq!(diagnostics.len(), 1);
+            assert_eq!(diagnostics[0].severity, check::Severity::Definite);
+            assert_eq!(diagnostics[0].call, patter!(call));
+        }
+    }
+
+    #[test]
+    fn check_guarded_intrinsic() {
+        let program = "((def ,pos? (#/fun/make `:true [,n]))
+                        (def ,r (all-matches (where ,x `(pos? x)) [1 2])))";
+        assert!(check(program, false).is_empty());
+    }
+
+    #[test]
+    fn check_innermost_call() {
+        let diagnostics =
+            check("((def ,x (#/add 1 :foo)) (list/head (fib 1 2)))", false);
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].call, patter!("(#/add 1 :foo)"));
+        assert!(matches!(
+            diagnostics[0].problem,
+            check::Problem::Fails { .. }
+        ));
+        assert_eq!(diagnostics[1].call, patter!("(fib 1 2)"));
+    }
+
+    #[test]
+    fn check_possible() {
+        let program = "(((\\ [1] `1) unknown))";
+        assert!(check(program, false).is_empty());
+        let diagnostics = check(program, true);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].severity, check::Severity::Possible);
+    }
+
+    #[test]
+    fn check_match_unreachable() {
+        let diagnostics = check("((match 1 [1 `:one] [1 `:again]))", false);
+        assert_eq!(diagnostics.len(), 1);
+        assert!(matches!(
+            diagnostics[0].problem,
+            check::Problem::Unreachable(1)
+        ));
+    }
+
+    #[test]
+    fn check_match_missing() {
+        let diagnostics = check(
+            "((\\ [,it] `(match it [[:some ,x] `x] [[:none] `0])))",
+            false,
+        );
+        assert_eq!(diagnostics.len(), 1);
+        assert!(matches!(diagnostics[0].problem, check::Problem::Missing(_)));
+    }
+
+    #[test]
+    fn check_duplicate_places() {
+        let duplicates = check::duplicate_places(
+            &patter!(
+                "((with? [,x ,x] 1 `x `0)
+                  (\\ [,y] `(with? [,z ,y] y `z `0)))"
+            )
+            .as_list()
+            .unwrap(),
+        );
+        assert_eq!(duplicates.len(), 1);
+        assert_eq!(duplicates[0].place, ident!("x"));
+    }
+
+    eval_test_std! {
+        missing_none,
+        "(ptn/missing [[:some any] [:none]] (~ [:some any] [:none]))",
+        patter!("()")
+    }
+    eval_test_std! {
+        missing_tag,
+        "(ptn/missing [[:some 1] [:none]] (~ [:some any] [:none]))",
+        patter_std!("[[:some (not 1)]]").unwrap()
+    }
+    eval_test_std! {
+        missing_range,
+        "(ptn/missing [(range 0 9) (range 20 255)] (range 0 255))",
+        patter_std!("[(#/ptn/range/make [:exclusive 9] [:exclusive 20])]").unwrap()
+    }
+    eval_test_std! {
+        redundant,
+        "(ptn/redundant [[any 1] [1 1] [,x ,y] [2 (range 0 ..)] (range 0 9) 5])",
+        patter!("(1 3 5)")
+    }
+    eval_test_std! {
+        exhaust_repeated_place,
+        "[(ptn/redundant [[,x ,x] [any any]]) (ptn/missing [[,x ,x]] [any any])]",
+        patter_std!("[[] [[any any]]]").unwrap()
+    }
+    eval_test_std! {
+        capture,
+        "(with? [1 (capture ,mid (many any)) 4] [1 2 3 4] `mid `never)",
+        patter!("(2 3)")
+    }
+
+    #[test]
+    fn capture_outside_list() {
+        assert!(matches!(
+            patter_std!("(with? (capture ,x 1) 1 `x `:no)")
+                .err()
+                .unwrap()
+                .info,
+            crate::error::InterpreterErrorInfo::CannotCompute(..)
+        ));
+    }
+    eval_test_std! {
+        regex_split,
+        r#"(with? (regex "(?<key>\w+)=(?<value>.*)") "key=value" `[key value] `never)"#,
+        patter_std!(r#"["key" "value"]"#).unwrap()
+    }
+    #[test]
+    fn regex_invalid_names() {
+        for source in &[r#""(?<->x)""#, r#""(?<9a>x)""#] {
+            assert!(matches!(
+                patter_std!(&format!("(regex {})", source))
+                    .err()
+                    .unwrap()
+                    .info,
+                crate::error::InterpreterErrorInfo::CannotConvert(..)
+            ));
+        }
+    }
+    eval_test_std! {
+        regex_lazy,
+        r#"(with? (regex "(?<key>.*?)=(?<value>.*)") "a=b=c" `[key value] `never)"#,
+        patter_std!(r#"["a" "b=c"]"#).unwrap()
+    }
+    eval_test_std! {
+        regex_counted,
+        r#"(id [
+            (with? (regex "[a-c]{2,3}\d") "abc1" `:true `:false)
+            (with? (regex "[a-c]{2,3}\d") "abcb1" `:true `:false)
+            (with? (regex "[^a-c]|x") "d" `:true `:false)
+        ])"#,
+        patter_std!("[:true :false :true]").unwrap()
+    }
+    eval_test_std! {
+        regex_alternative_any,
+        r#"(id [
+            (with? (regex ".|ab") "ab" `:true `:false)
+            (with? (regex ".|ab") "a" `:true `:false)
+            (with? (regex "(?:.|ab)c") "abc" `:true `:false)
+        ])"#,
+        patter_std!("[:true :true :true]").unwrap()
+    }
+    eval_test_std! {
+        somewhere_first,
+        "(with? (somewhere [:x ,v]) [:y [:z [:x 5]] [:x 6]] `v `never)",
+        number!(5)
+    }
+    eval_test_std! {
+        somewhere_nowhere,
+        "(with? (somewhere 7) [1 [2 `3]] `:true `:false)",
+        patter_std!(":false").unwrap()
+    }
+    eval_test_std! {
+        subset_somewhere,
+        "(ptn/subset? [:x 1] (somewhere [:x any]))",
+        patter_std!(":true").unwrap()
+    }
+    eval_test_std! {
+        all_matches,
+        "(all-matches [:x ,v] [[:x 1] [:y [:x 2]] [:x 3]])",
+        patter!("(((:v 1)) ((:v 2)) ((:v 3)))")
+    }
+    eval_test_std! {
+        rewrite_bottom_up,
+        "(rewrite [[[:add ,a ,b] `(#/add a b)]] :bottom-up [:add 1 [:add 2 3]])",
+        number!(6)
+    }
+    eval_test_std! {
+        rewrite_top_down,
+        "(rewrite [[[:wrap [:wrap ,x]] `[:wrap x]]] :top-down [:wrap [:wrap [:wrap 1]]])",
+        patter_std!("[:wrap 1]").unwrap()
+    }
+    eval_test_std! {
+        rewrite_fixpoint,
+        "(rewrite [[[:add 0 ,x] `x] [[:add ,x 0] `x]] :bottom-up [[:add [:add 0 :a] 0] :b])",
+        patter_std!("[:a :b]").unwrap()
+    }
+    eval_test_std! {
+        rewrite_unchanged,
+        "(rewrite [[[:a ,x] `[:a x]]] :top-down [:a [:a 1]])",
+        patter_std!("[:a [:a 1]]").unwrap()
+    }
+    eval_test_std! {
+        rewrite_unchanged_patterns,
+        "(rewrite [[[:keep ,p] `[:keep p]]] :top-down [:keep (~ 1 2)])",
+        patter_std!("[:keep (~ 1 2)]").unwrap()
+    }
+    eval_test_std! {
+        rewrite_unchanged_tries_next_rule,
+        "(rewrite [[[:a ,x] `[:a x]] [[:a 1] `:done]] :top-down [:a 1])",
+        patter_std!(":done").unwrap()
+    }
+
+    #[test]
+    fn rewrite_endless() {
+        assert!(matches!(
+            patter_std!("(rewrite [[[:a ,x] `[:b x]] [[:b ,x] `[:a x]]] :bottom-up [:a 1])")
+                .err()
+                .unwrap()
+                .info,
+            crate::error::InterpreterErrorInfo::CannotCompute(..)
+        ));
+    }
+    eval_test_std! {
+        quasiquote_unquote,
+        "(def ,x 5) |[a @x]",
+        patter!("[a 5]")
+    }
+    eval_test_std! {
+        quasiquote_splice,
+        "(def ,xs [1 2]) |(f &@xs 3 &ys)",
+        patter!("(f 1 2 3 &ys)")
+    }
+    eval_test_std! {
+        quasiquote_body,
+        "(def ,n 2) (def ,f (#/fun/make |(#/add @n x) [,x])) (f 1)",
+        number!(3)
+    }
+    eval_test_std! {
+        quasiquote_nested,
+        "(def ,x 1) ||[@@x @x]",
+        patter!("|[@1 @x]")
+    }
+    eval_test_std! {
+        macro_args_unevaluated,
+        "(defmacro ,my-if [,c ,a ,b] `|(if @c `@a `@b)) (my-if :true 1 never)",
+        number!(1)
+    }
+    eval_test_std! {
+        macro_hygiene,
+        "(defmacro ,with-tmp [,body] `|(let `((,tmp 1)) `[tmp @body]))
+        (def ,tmp 5)
+        (with-tmp tmp)",
+        patter_std!("[1 5]").unwrap()
+    }
+    eval_test_std! {
+        macro_binds_name,
+        "(defmacro ,def-five [] `|(def ,@`five 5)) (def-five) five",
+        number!(5)
+    }
+    eval_test_std! {
+        macroexpand,
+        "(defmacro ,my-if [,c ,a ,b] `|(if @c `@a `@b))
+        (def ,code `(my-if :true 1 2))
+        [code (macroexpand code)]",
+        patter_std!("[`(my-if :true 1 2) `(if :true `1 `2)]").unwrap()
+    }
+    eval_test_std! {
+        macro_quoted_data,
+        "(defmacro ,twice [,x] `|[@x @x])
+        [`(twice 1)
+         (with? `(twice 3) [`twice 3] `:data `:code)
+         ((\\ [,y] `(twice y)) 2)
+         (match 4 [,w `(twice w)])]",
+        patter_std!("[`(twice 1) :data [2 2] [4 4]]").unwrap()
+    }
+    eval_test_std! {
+        fresh_ident_unique,
+        "(def ,a (ident/fresh `x))
+        [(with? a a `:same `:different)
+         (with? a `x `:same `:different)
+         (with? a (ident/fresh `x) `:same `:different)]",
+        patter_std!("[:same :different :different]").unwrap()
+    }
+    eval_test_std! {
+        arithmetic_integers,
+        "[(sub 3 5) (mul -4 6) (quot -7 2) (rem -7 2) (mod -7 2) (pow 2 10) (neg 5) (abs -5)]",
+        patter_std!("[-2 -24 -3 -1 1 1024 -5 5]").unwrap()
+    }
+    eval_test_std! {
+        arithmetic_rationals,
+        "[(div 6 3) (mul (div 1 3) 3) (add (div 1 2) (div 1 3)) (pow 2 -2) (mod (div 7 2) 2)]",
+        patter_std!("[2 1 (div 5 6) (div 1 4) (div 3 2)]").unwrap()
+    }
+
+    #[test]
+    fn div_by_zero() {
+        assert!(matches!(
+            patter_std!("(div 1 0)").err().unwrap().info,
+            crate::error::InterpreterErrorInfo::CannotCompute(..)
+        ));
+    }
+
+    #[test]
+    fn ident_from_int() {
+        assert_eq!(
+            patter_std!("(ident/from-int (abs 3))").unwrap(),
+            patter_std!("(ident/from-int 3)").unwrap()
+        );
+        for code in &["(ident/from-int -3)", "(ident/from-int (div 1 2))"] {
+            assert!(matches!(
+                patter_std!(code).err().unwrap().info,
+                crate::error::InterpreterErrorInfo::CannotConvert(..)
+            ));
+        }
+    }
+
+    #[test]
+    fn precision_sub() {
+        let code_point = |c: char| c.into_sexpr().as_number().unwrap();
+        let max = BigInt::from(2_u64.pow(21) - 1);
+        assert_eq!(
+            code_point('b')
+                .checked_sub(&code_point('a'))
+                .unwrap()
+                .precision,
+            Precision::integer(-max.clone(), max)
+        );
+    }
+    eval_test_std! {
+        comparisons,
+        "[(lt 1 2) (le 2 2) (gt (div 1 3) (div 1 2)) (ge -1 (neg 1)) (num-eq (div 4 2) 2)]",
+        patter_std!("[:true :true :false :true :true]").unwrap()
+    }
+    eval_test_std! {
+        compare,
+        "[(compare 1 (div 3 2)) (compare (mul 2 3) 6) (compare 0 -7)]",
+        patter_std!("[:less :equal :greater]").unwrap()
+    }
+
+    #[test]
+    fn compare_float() {
+        use crate::number::{Bound, Number, Range};
+        let float = |significand: isize, exponent: isize| Number {
+            rep: NumberRep::ArbitraryFloat {
+                significand: Box::new(Number::from(significand)),
+                exponent: BigInt::from(exponent),
+                base: 10_u32.into(),
+            },
+            precision: Precision::Float {
+                base: 10_u32.into(),
+                significand_range: Range::new(
+                    Bound::Unbounded,
+                    Bound::Unbounded,
+                ),
+                exponent_range: Range::new(Bound::Unbounded, Bound::Unbounded),
+            },
+        };
+        let quarter = Number::from(1).checked_div(&Number::from(4)).unwrap();
+        assert_eq!(float(25, -2), quarter);
+        assert!(float(3, 1) > Number::from(29));
+        assert!(float(-5, 0) < quarter);
+    }
+    eval_test_std! {
+        float_decimal,
+        "(def ,decimal (\\ [,x]
+            `(float x 10 (range -9999999 9999999) (range -20 20) :nearest-even)))
+        (def ,binary (\\ [,x]
+            `(float x 2 (range -9007199254740991 9007199254740991) (range -1074 971) :nearest-even)))
+        [(num-eq (add (decimal (div 1 10)) (decimal (div 2 10))) (div 3 10))
+         (num-eq (add (binary (div 1 10)) (binary (div 2 10))) (div 3 10))]",
+        patter_std!("[:true :false]").unwrap()
+    }
+    eval_test_std! {
+        float_rounding,
+        "(def ,two-digits (\\ [,x ,rounding]
+            `(float x 10 (range -99 99) (range .. ..) rounding)))
+        (list/map
+            (\\ [,it] `(float/to-rational it))
+            [(two-digits (div 2 3) :nearest-even)
+             (two-digits (div 2 3) :toward-zero)
+             (two-digits (div -2 3) :toward-zero)
+             (two-digits (div -2 3) :up)
+             (two-digits (div -2 3) :down)
+             (two-digits (div 125 1000) :nearest-even)
+             (two-digits (div 135 1000) :nearest-even)
+             (float/add (two-digits 1 :down) (two-digits (div 1 1000) :down) :up)])",
+        patter_std!("[(div 67 100) (div 66 100) (div -66 100) (div -66 100) (div -67 100)
+                      (div 12 100) (div 14 100) (div 11 10)]").unwrap()
+    }
+
+    #[test]
+    fn float_errors() {
+        let message = |code| match patter_std!(code).err().unwrap().info {
+            crate::error::InterpreterErrorInfo::CannotCompute(msg, _) => msg,
+            info => panic!("Unexpected error: {}", info),
+        };
+        assert_eq!(
+            message(
+                "(add (float 99 10 (range -99 99) (range 0 0) :nearest-even) 1)"
+            ),
+            "The result is out of the range of the float precision"
+        );
+        assert_eq!(
+            message(
+                "(float/add
+                    (float 1 10 (range -99 99) (range .. ..) :nearest-even)
+                    (float 1 2 (range -99 99) (range .. ..) :nearest-even)
+                    :up)"
+            ),
+            "Can only round floats of the same base"
+        );
+    }
+
+    #[test]
+    fn float_to_ident() {
+        let float = |x| {
+            format!(
+                "(ident/from-int (float {} 10 (range -99 99) (range -5 5) :nearest-even))",
+                x
+            )
+        };
+        assert_eq!(
+            patter_std!(&float("3")).unwrap(),
+            patter_std!("(ident/from-int 3)").unwrap()
+        );
+        assert!(matches!(
+            patter_std!(&float("(div 1 2)")).err().unwrap().info,
+            crate::error::InterpreterErrorInfo::CannotConvert(..)
+        ));
+    }
+
+    #[test]
+    fn f64_round_trip() {
+        use crate::number::Number;
+        for float in [0.1, -2.5, 5e-324, f64::MIN_POSITIVE, f64::MAX] {
+            assert_eq!(Number::from_f64(float).unwrap().to_f64(), Some(float));
+        }
+        let third = Number::from(1).checked_div(&Number::from(3)).unwrap();
+        assert_eq!(third.to_f64(), Some(1.0 / 3.0));
+        let tenth = Number::from(1).checked_div(&Number::from(10)).unwrap();
+        assert!(Number::from_f64(0.1).unwrap() != tenth);
+    }
+    fn rational_precision(
+        numerators: (number::Bound<BigInt>, number::Bound<BigInt>),
+        denominators: (u32, u32),
+    ) -> Precision {
+        use number::{
+            Bound, PositiveIntegerOrInvPositiveInteger::Integer, Range,
+        };
+        Precision::Rational {
+            numerator_range: Range::new(numerators.0, numerators.1),
+            denominator_range: Range::new(
+                Bound::Inclusive(Integer(denominators.0.into())),
+                Bound::Inclusive(Integer(denominators.1.into())),
+            ),
+        }
+    }
+
+    fn decimal_precision(digits: u32) -> Precision {
+        use number::{Bound, Number, Range};
+        let most = 10_isize.pow(digits) - 1;
+        Precision::float(
+            10_u32.into(),
+            &Range::new(
+                Bound::Inclusive(Number::from(-most)),
+                Bound::Inclusive(Number::from(most)),
+            ),
+            &Range::new(
+                Bound::Inclusive(Number::from(-2)),
+                Bound::Inclusive(Number::from(2)),
+            ),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn precision_bound_kinds() {
+        use number::Bound::*;
+        let below_ten = rational_precision(
+            (Inclusive(0.into()), Exclusive(10.into())),
+            (1, 1),
+        );
+        let above_four =
+            rational_precision((Exclusive(4.into()), Unbounded), (1, 1));
+        assert_eq!(
+            Precision::add(&below_ten, &above_four),
+            rational_precision((Inclusive(5.into()), Unbounded), (1, 1))
+        );
+        assert_eq!(
+            Precision::sub(&below_ten, &above_four),
+            rational_precision((Unbounded, Inclusive(4.into())), (1, 1))
+        );
+        assert_eq!(
+            Precision::mul(
+                &Precision::integer((-2).into(), 3.into()),
+                &Precision::integer(4.into(), 5.into())
+            ),
+            Precision::integer((-10).into(), 15.into())
+        );
+        assert_eq!(
+            Precision::div(
+                &Precision::integer(1.into(), 6.into()),
+                &Precision::integer(2.into(), 3.into())
+            ),
+            rational_precision(
+                (Inclusive(1.into()), Inclusive(6.into())),
+                (2, 3)
+            )
+        );
+        assert_eq!(
+            Precision::integer(1.into(), 6.into()).neg(),
+            Precision::integer((-6).into(), (-1).into())
+        );
+    }
+
+    #[test]
+    fn precision_floats() {
+        use number::Bound::*;
+        let bit = Precision::integer(0.into(), 1.into());
+        assert_eq!(
+            Precision::add(&decimal_precision(3), &bit),
+            decimal_precision(3)
+        );
+        assert_eq!(
+            Precision::mul(&decimal_precision(3), &decimal_precision(5)),
+            decimal_precision(5)
+        );
+        assert!(matches!(
+            Precision::add(&decimal_precision(3), &Precision::f64()),
+            Precision::Rational { .. }
+        ));
+        assert_eq!(
+            decimal_precision(3).rational(),
+            rational_precision(
+                (Inclusive((-99900).into()), Inclusive(99900.into())),
+                (1, 100)
+            )
+        );
+    }
+
+    #[test]
+    fn precision_order() {
+        let narrow = Precision::integer(0.into(), 5.into());
+        let wide = Precision::integer(0.into(), 10.into());
+        assert!(narrow < wide);
+        assert!(wide > narrow);
+        let thirds =
+            Precision::div(&narrow, &Precision::integer(3.into(), 3.into()));
+        assert_eq!(thirds.partial_cmp(&wide), None);
+        assert!(decimal_precision(3) < decimal_precision(5));
+        assert_eq!(decimal_precision(3).partial_cmp(&Precision::f64()), None);
+        assert_eq!(decimal_precision(3).partial_cmp(&wide), None);
+    }
+    eval_test_std! {
+        regex_repeated_capture,
+        r#"(with? (regex "(?:(?<d>\d),?)*") "1,2,3" `d `never)"#,
+        patter_std!(r#"["1" "2" "3"]"#).unwrap()
+    }
+    eval_test_std! {subset_literal, "(ptn/subset? 1 any)", patter_std!(":true").unwrap()}
+    eval_test_std! {subset_any, "(ptn/subset? any 1)", patter_std!(":false").unwrap()}
+    eval_test_std! {
+        subset_split_union,
+        "(ptn/subset? [(~ 1 2) ,x] (~ [1 any] [2 any]))",
+        patter_std!(":true").unwrap()
+    }
+    eval_test_std! {
+        subset_union_missing,
+        "(ptn/subset? [(~ 1 2 3) ,x] (~ [1 any] [2 any]))",
+        patter_std!(":false").unwrap()
+    }
+    eval_test_std! {
+        subset_unknown_accumulator,
+        "(def ,last-acc [(\\ [,a ,b] `b) [:none]])
+         [(with? (#/ptn/acc/make last-acc [1 2]) 2 `:yes `:no)
+          (ptn/subset? (#/ptn/acc/make last-acc [1 2]) 1)]",
+        patter_std!("[:yes :unknown]").unwrap()
+    }
+    eval_test_std! {
+        subset_kleene,
+        "(ptn/subset? [(many 1) 2] [(many any) (~ 2 3)])",
+        patter_std!(":true").unwrap()
+    }
+    eval_test_std! {
+        subset_intersection,
+        "(ptn/subset? (^ [1 any] [any 2]) [1 any])",
+        patter_std!(":true").unwrap()
+    }
+    eval_test_std! {
+        disjoint_lengths,
+        "(ptn/disjoint? [any] [any any])",
+        patter_std!(":true").unwrap()
+    }
+    eval_test_std! {
+        disjoint_kleene,
+        "[(ptn/disjoint? [(many 1)] [(~ 2 3)]) (ptn/disjoint? [(many 1)] [any 1])]",
+        patter_std!("[:true :false]").unwrap()
+    }
+    eval_test_std! {
+        disjoint_consec,
+        "(ptn/disjoint? [(consec 1 2) any] [1 3 any])",
+        patter_std!(":true").unwrap()
+    }
+    eval_test_std! {
+        subset_repeated_place,
+        "[(ptn/subset? [any any] [,x ,x])
+          (ptn/subset? [1 any] [,x ,x])
+          (ptn/subset? [1 1] [,x ,x])
+          (ptn/subset? [1 2] [,x ,x])
+          (ptn/subset? [,x ,x] [any any])
+          (ptn/subset? [:a any] (~ [:a ,y] [:b ,y]))
+          (ptn/disjoint? [1 2] [,x ,x])
+          (ptn/disjoint? [(~ 1 2) 3] [,x ,x])]",
+        patter_std!("[:false :false :true :false :true :true :true :unknown]").unwrap()
+    }
+    // Only a place opposite a repeated place is taken to show that a pattern
+    // isn't a subset; other patterns matching several values aren't
+    eval_test_std! {
+        subset_repeated_place_undecided,
+        "[(ptn/subset? [(~ 1 2) (~ 1 2)] [,x ,x])
+          (ptn/subset? [any [any]] [,x [,x]])]",
+        patter_std!("[:unknown :unknown]").unwrap()
+    }
+    eval_test_std! {
+        not_in_list,
+        "[(with? [(not 1) ,x] [2 3] `x `never) (with? [(not 1)] [1] `:yes `:no)]",
+        patter_std!("[3 :no]").unwrap()
+    }
+    eval_test_std! {
+        minus,
+        "[(with? (minus [,x any] [1 any]) [2 3] `x `never) (with? (minus [,x any] [1 any]) [1 3] `x `:no)]",
+        patter_std!("[2 :no]").unwrap()
+    }
+    eval_test_std! {
+        subset_not,
+        "[(ptn/subset? (minus [any] [1]) (not [1])) (ptn/subset? (not [any]) (not [1]))]",
+        patter_std!("[:true :true]").unwrap()
+    }
+    eval_test_std! {
+        disjoint_not,
+        "(ptn/disjoint? [1 any] (not [1 any]))",
+        patter_std!(":true").unwrap()
+    }
+    eval_test_std! {
+        guard_with,
+        "[
+            (with? (where [,a ,b] `(with? a b `:true `:false)) [1 1] `a `:no)
+            (with? (where [,a ,b] `(with? a b `:true `:false)) [1 2] `a `:no)
+        ]",
+        patter_std!("[1 :no]").unwrap()
+    }
+    eval_test_std! {
+        guard_args,
+        "(def ,pair? (clauses
+            (\\ [(where ,x `(with? [any any] x `:true `:false))] `:pair)
+            (\\ [any] `:other)
+         ))
+         [(pair? [1 2]) (pair? [1 2 3])]",
+        patter!("(:pair :other)")
+    }
+
+    #[test]
+    fn guard_not_truth() {
+        assert!(matches!(
+            patter_std!("(with? (where ,x `1) 1 `:yes `:no)")
+                .err()
+                .unwrap()
+                .info,
+            crate::error::InterpreterErrorInfo::CannotConvert(..)
+        ));
+    }
+    eval_test_std! {
+        range_inclusive,
+        "[(with? (range 0 255) 255 `:yes `:no) (with? (range 0 255) 256 `:yes `:no)]",
+        patter_std!("[:yes :no]").unwrap()
+    }
+    eval_test_std! {
+        range_unbounded,
+        "[(with? [(range 1 ..) ,x] [1000 2] `x `:no) (with? (range 1 ..) -3 `:yes `:no)]",
+        patter_std!("[2 :no]").unwrap()
+    }
+    eval_test_std! {
+        range_exclusive,
+        "[(with? (range-exclusive 0 1) 0 `:yes `:no) (with? (range-exclusive 0 1) 1 `:yes `:no)]",
+        patter_std!("[:yes :no]").unwrap()
+    }
+    eval_test_std! {
+        subset_range,
+        "[
+            (ptn/subset? (range 0 255) (range 0 ..))
+            (ptn/subset? (range 0 255) (range-exclusive 0 255))
+            (ptn/disjoint? (range-exclusive 0 1) (range 1 2))
+            (ptn/disjoint? 3 (range .. 2))
+        ]",
+        patter_std!("[:true :false :true :true]").unwrap()
+    }
+    eval_test_std! {
+        record_any_order,
+        "[
+            (with? (record [:a ,a] [:b ,b]) [[:b 2] [:a 1]] `[a b] `:no)
+            (with? (record [:a ,a] [:b ,b]) [[:a 1]] `[a b] `:no)
+        ]",
+        patter_std!("[[1 2] :no]").unwrap()
+    }
+    eval_test_std! {
+        record_default,
+        "(with? (record [:a ,a] [:c ,c 3]) [[:a 1]] `[a c] `:no)",
+        patter_std!("[1 3]").unwrap()
+    }
+    eval_test_std! {
+        record_rest,
+        "(with? (record/rest ,others [:a ,a]) [[:b 2] [:a 1] [:c 3]] `others `:no)",
+        patter_std!("[[:b 2] [:c 3]]").unwrap()
+    }
+    eval_test_std! {
+        repeated_place,
+        "[(with? [,x ,x] [1 2] `x `:no) (with? [,x [,x]] [1 [1]] `x `:no)]",
+        patter_std!("[:no 1]").unwrap()
+    }
+    eval_test_std! {
+        repeated_place_backtracks,
+        "(with? [(many any) ,x (many any) ,x (many any)] [1 2 3 2 4] `x `:no)",
+        number!(2)
+    }
+    eval_test_std! {
+        repeated_place_intersection,
+        "[(with? (^ [,x any] [any ,x]) [1 2] `x `:no) (with? (^ [,x any] [any ,x]) [3 3] `x `:no)]",
+        patter_std!("[:no 3]").unwrap()
+    }
+    eval_test_std! {
+        same,
+        "(with? (where [,a ,b] `(same a b)) [[1 2] [1 2]] `a `:no)",
+        patter_std!("[1 2]").unwrap()
+    }
+
+    #[test]
+    fn kleene_not_exponential() {
+        let ptn =
+            "[(many any) :foo (many any) :foo (many any) :foo (many any) :bar]";
+        let list = vec![":foo"; 200].join(" ");
+        assert_eq!(
+            patter_std!(&format!("(with? {} [{}] `:yes `:no)", ptn, list))
+                .unwrap(),
+            patter_std!(":no").unwrap()
+        );
+    }
+
+    #[test]
+    fn clauses_no_match() {
+        assert!(matches!(
+            patter_std!("((clauses (\\ [0] `0) (\\ [1] `1)) 2)")
+                .err()
+                .unwrap()
+                .info,
+            crate::error::InterpreterErrorInfo::NonMatchingArgs(..)
+        ));
+    }
+
+    #[test]
+    fn match_no_arm() {
+        assert_eq!(
+            patter_std!("(match 5 [0 `:zero] [1 `:one])")
+                .err()
+                .unwrap()
+                .info,
+            crate::error::InterpreterErrorInfo::NoMatchingArm(
+                number!(5),
+                vec![number!(0), number!(1)]
+            )
+        );
+    }
+
+    #[test]
+    fn let_binds_not_list() {
+        assert_eq!(
+            patter_std!("(let 5 `1)").err().unwrap().info,
+            crate::error::InterpreterErrorInfo::NotA(
+                crate::SExprKind::List,
+                number!(5)
+            )
+        );
+    }
+
+    #[test]
+    fn match_bindings_are_local() {
+        let mut cxt = STD_CXT.clone();
+        patter!("(match 5 [,foo `foo])").eval(&mut cxt).unwrap();
+        assert_eq!(
+            patter!("foo").eval(&mut cxt).err().unwrap().info,
+            crate::error::InterpreterErrorInfo::UnknownName(ident!("foo"))
+        );
+    }
+
+    #[test]
+    fn def_in_function_is_local() {
+        let mut cxt = STD_CXT.clone();
+        patter!("((\\ [] `[(def ,foo 5)]))").eval(&mut cxt).unwrap();
+        assert_eq!(
+            patter!("foo").eval(&mut cxt).err().unwrap().info,
+            crate::error::InterpreterErrorInfo::UnknownName(ident!("foo"))
+        );
+    }
+
+    #[test]
+    fn never_panics() {
+        assert_eq!(
+            patter_std!("never").err().unwrap().info,
+            crate::error::InterpreterErrorInfo::ReachedTheUnreachable
+        );
+    }
+
     #[test]
     fn match_ptn_bindings() {
         assert_eq!(
@@ -1153,6 +2757,15 @@ mod tests {
         )
     }
 
+    #[test]
+    fn convert_none() {
+        assert_eq!(
+            Option::<Bindings>::from_sexpr(None::<Bindings>.into_sexpr())
+                .unwrap(),
+            None,
+        )
+    }
+
     #[test]
     fn context() {
         let _ = Context::new();
@@ -0,0 +1,597 @@
+//! Matching list patterns without exponential backtracking.
+//!
+//! A list pattern is compiled into a graph of nodes, each standing for "the
+//! rest of the pattern from here on". What the rest of a pattern does from a
+//! given position in the list doesn't depend on how the pattern got there, so
+//! the result of matching each node at each position is only worked out once.
+//! Where a pattern could match in several ways, the nodes are tried in order
+//! of preference, so the memoized result is the one backtracking would find.
+//! A place bound more than once has to be bound to equal values, so what was
+//! bound to such places earlier is memoized along with the node and position.
+//!
+//! A greedy Kleene pattern prefers taking another repetition to stopping, and
+//! a lazy one the reverse. A possessive one takes repetitions while they
+//! match, each as long as it can on its own, and never reconsiders them.
+//! Repetitions are matched on their own, so the places they bind can be
+//! collected into lists; greedy and possessive repetitions try their longest
+//! match first, and lazy ones their shortest.
+//!
+//! A capture's pattern is matched in line with the rest of the list, so that
+//! it prefers what the pattern would on its own. Where the capture started is
+//! kept with the values of repeated places until its end is reached.
+
+use std::collections::HashMap;
+use std::iter;
+
+use num::ToPrimitive;
+
+use crate::context::{Bindings, Context};
+use crate::error::InterpreterError;
+use crate::intern::Interned;
+use crate::number::{Number, NumberRep};
+use crate::parse;
+use crate::{FromSExpr, Fun, Ident, IntoSExpr, KleeneMode, RecordField, SExpr};
+
+type NodeId = usize;
+
+const END: NodeId = 0;
+
+/// The values bound to repeated places before a node, and the result of
+/// matching from it
+type Memoized = (Bindings, Option<Bindings>);
+
+enum Node {
+    End,
+    /// A singular pattern, matching one element
+    Item(SExpr, NodeId),
+    /// Matches the next element against the pattern if it is zero-width,
+    /// otherwise consumes nothing
+    ZeroWidth(SExpr, NodeId),
+    /// The `iteration`th repetition of a Kleene pattern
+    Kleene {
+        kleene: usize,
+        iteration: usize,
+        rest: NodeId,
+        /// The node for the following repetition, once built
+        next: Option<NodeId>,
+    },
+    /// The start of a capture, which notes its position under `marker`
+    /// before matching `inner`
+    Capture {
+        marker: Interned<'static, Ident>,
+        inner: NodeId,
+    },
+    /// The end of a capture, binding `name` to the elements since its start
+    CaptureEnd {
+        name: Interned<'static, Ident>,
+        marker: Interned<'static, Ident>,
+        rest: NodeId,
+    },
+    /// A non-singular `PtnAcc`, each of whose patterns is matched along with
+    /// the rest of the list
+    Acc {
+        acc: Fun,
+        init: Option<Bindings>,
+        alternatives: Vec<NodeId>,
+    },
+}
+
+/// The patterns a Kleene pattern has generated so far, shared by all of its
+/// repetitions
+struct KleeneGen {
+    pats: Vec<SExpr>,
+    start_len: usize,
+    next: Fun,
+    mode: KleeneMode,
+    /// The places collected into lists, once worked out
+    collected: Option<Vec<Interned<'static, Ident>>>,
+}
+
+struct Matcher<'a> {
+    exprs: &'a [SExpr],
+    nodes: Vec<Node>,
+    kleenes: Vec<KleeneGen>,
+    /// The places bound more than once by the pattern
+    repeated: Vec<Interned<'static, Ident>>,
+    /// The result of each node at each position, for each set of values
+    /// already bound to repeated places
+    memo: HashMap<(NodeId, usize), Vec<Memoized>>,
+}
+
+/// Matches the elements of a list pattern against the elements of a list.
+/// Where the same place is bound more than once, the values must be equal,
+/// except that places bound by every repetition of a Kleene pattern are
+/// collected into a list with an element per repetition.
+pub fn match_list(
+    pats: &[SExpr],
+    exprs: &[SExpr],
+) -> Result<Option<Bindings>, InterpreterError> {
+    let mut seen = Vec::new();
+    let mut repeated = Vec::new();
+    for place in pats.iter().flat_map(SExpr::places) {
+        if place == ident!("#/noread") || repeated.contains(&place) {
+            continue;
+        }
+        if seen.contains(&place) {
+            repeated.push(place);
+        } else {
+            seen.push(place);
+        }
+    }
+    let mut matcher = Matcher {
+        exprs,
+        nodes: vec![Node::End],
+        kleenes: Vec::new(),
+        repeated,
+        memo: HashMap::new(),
+    };
+    let start = matcher.build(pats, END)?;
+    matcher.match_from(start, 0, &Bindings::empty())
+}
+
+/// Matches a record pattern against a list of `[key value]` pairs. Each field
+/// takes the first pair with its key that no earlier field took.
+pub fn match_record(
+    fields: &[RecordField],
+    rest: Option<&SExpr>,
+    exprs: &[SExpr],
+) -> Result<Option<Bindings>, InterpreterError> {
+    let mut pairs = Vec::new();
+    for expr in exprs {
+        match expr {
+            SExpr::List(pair) if pair.len() == 2 => {
+                pairs.push((&pair[0], &pair[1]))
+            }
+            _ => return Ok(None),
+        }
+    }
+    let mut taken = vec![false; pairs.len()];
+    let mut bindings = Bindings::empty();
+    for field in fields {
+        let pair =
+            (0..pairs.len()).find(|&i| !taken[i] && *pairs[i].0 == field.key);
+        let value = match (pair, &field.default) {
+            (Some(i), _) => {
+                taken[i] = true;
+                pairs[i].1
+            }
+            (None, Some(default)) => default,
+            (None, None) => return Ok(None),
+        };
+        match field.ptn.match_ptn(value)?.and_then(|b| b.unify(&bindings)) {
+            Some(field_bindings) => bindings = field_bindings,
+            None => return Ok(None),
+        }
+    }
+    if let Some(rest) = rest {
+        let untaken = exprs
+            .iter()
+            .zip(&taken)
+            .filter(|(_, taken)| !**taken)
+            .map(|(expr, _)| expr.clone())
+            .collect();
+        match rest
+            .match_ptn(&SExpr::List(untaken))?
+            .and_then(|b| b.unify(&bindings))
+        {
+            Some(rest_bindings) => bindings = rest_bindings,
+            None => return Ok(None),
+        }
+    }
+    Ok(Some(bindings))
+}
+
+impl Matcher<'_> {
+    fn push(&mut self, node: Node) -> NodeId {
+        self.nodes.push(node);
+        self.nodes.len() - 1
+    }
+
+    /// Builds the nodes for `pats` followed by `rest`
+    fn build(
+        &mut self,
+        pats: &[SExpr],
+        rest: NodeId,
+    ) -> Result<NodeId, InterpreterError> {
+        use SExpr::*;
+        let mut node = rest;
+        for pat in pats.iter().rev() {
+            node = match pat {
+                pat if pat.matches_singular() => {
+                    self.push(Node::Item(pat.clone(), node))
+                }
+                Consecutive(pats) => self.build(pats, node)?,
+                ZeroWidth(inner) => {
+                    self.push(Node::ZeroWidth((**inner).clone(), node))
+                }
+                Kleene { start, next, mode } => {
+                    let pats =
+                        start.clone().as_list().ok_or(interpreter_err!(
+                            NotA,
+                            crate::SExprKind::List,
+                            (**start).clone()
+                        ))?;
+                    self.kleenes.push(KleeneGen {
+                        start_len: pats.len(),
+                        pats,
+                        next: next.clone(),
+                        mode: *mode,
+                        collected: None,
+                    });
+                    self.push(Node::Kleene {
+                        kleene: self.kleenes.len() - 1,
+                        iteration: 0,
+                        rest: node,
+                        next: None,
+                    })
+                }
+                Capture { name, ptn } => {
+                    let marker = ident!(&format!(
+                        "#/capture/start-{}",
+                        self.nodes.len()
+                    ));
+                    let end = self.push(Node::CaptureEnd {
+                        name: *name,
+                        marker,
+                        rest: node,
+                    });
+                    let inner = self.build(std::slice::from_ref(ptn), end)?;
+                    self.push(Node::Capture { marker, inner })
+                }
+                PtnAcc { acc, init, pats } => {
+                    let alternatives = pats
+                        .iter()
+                        .map(|pat| self.build(std::slice::from_ref(pat), node))
+                        .collect::<Result<_, _>>()?;
+                    self.push(Node::Acc {
+                        acc: acc.clone(),
+                        init: init.clone(),
+                        alternatives,
+                    })
+                }
+                AtPtnTime(fun) => {
+                    let pat = fun
+                        .clone()
+                        .as_fun()
+                        .ok_or(interpreter_err!(CannotCall, (**fun).clone()))?
+                        .call(vec![], &mut Context::empty())?;
+                    self.build(&[pat], node)?
+                }
+                pat => unreachable!("Not a list element pattern: {:?}", pat),
+            };
+        }
+        Ok(node)
+    }
+
+    /// Matches from `node` at `pos`, given the values `known` to have been
+    /// bound to repeated places before it
+    fn match_from(
+        &mut self,
+        node: NodeId,
+        pos: usize,
+        known: &Bindings,
+    ) -> Result<Option<Bindings>, InterpreterError> {
+        if let Some((_, result)) = self
+            .memo
+            .get(&(node, pos))
+            .and_then(|results| results.iter().find(|(k, _)| k == known))
+        {
+            return Ok(result.clone());
+        }
+        let exprs = self.exprs;
+        let result = match &self.nodes[node] {
+            Node::End => (pos == exprs.len()).then(Bindings::empty),
+            Node::Item(pat, rest) => {
+                let rest = *rest;
+                match exprs.get(pos) {
+                    Some(expr) => match pat.match_ptn(expr)? {
+                        Some(bindings) => {
+                            self.match_then(rest, pos + 1, known, &bindings)?
+                        }
+                        None => None,
+                    },
+                    None => None,
+                }
+            }
+            Node::ZeroWidth(pat, rest) => {
+                let rest = *rest;
+                match exprs.get(pos) {
+                    Some(SExpr::ZeroWidth(expr)) => {
+                        match pat.match_ptn(expr)? {
+                            Some(bindings) => self.match_then(
+                                rest,
+                                pos + 1,
+                                known,
+                                &bindings,
+                            )?,
+                            None => None,
+                        }
+                    }
+                    _ => self.match_from(rest, pos, known)?,
+                }
+            }
+            Node::Kleene {
+                kleene,
+                iteration,
+                rest,
+                ..
+            } => {
+                let (kleene, iteration, rest) = (*kleene, *iteration, *rest);
+                let KleeneGen {
+                    start_len, mode, ..
+                } = self.kleenes[kleene];
+                let required = iteration < start_len;
+                let collected = self.collected(kleene)?;
+                let stop_first = mode == KleeneMode::Lazy && !required;
+                let mut result = if stop_first {
+                    self.stop(rest, pos, known, &collected)?
+                } else {
+                    None
+                };
+                let mut took = false;
+                if result.is_none() {
+                    let pat = self.pattern(kleene, iteration)?;
+                    let mut repetitions = self.repetitions(&pat, pos)?;
+                    // Repetitions that consume nothing can't get us anywhere
+                    // new, and would be collected forever
+                    if !required {
+                        repetitions.retain(|(end, _)| *end > pos);
+                    }
+                    match mode {
+                        KleeneMode::Greedy => {}
+                        KleeneMode::Lazy => repetitions.reverse(),
+                        KleeneMode::Possessive => repetitions.truncate(1),
+                    }
+                    took = !repetitions.is_empty();
+                    let next = self.next(node);
+                    for (end, bindings) in repetitions {
+                        let known =
+                            match self.learn(known, &bindings, &collected) {
+                                Some(known) => known,
+                                None => continue,
+                            };
+                        result = self
+                            .match_from(next, end, &known)?
+                            .and_then(|r| collect(&collected, &bindings, r));
+                        if result.is_some() {
+                            break;
+                        }
+                    }
+                }
+                let gave_back = mode != KleeneMode::Possessive || !took;
+                if result.is_none() && !stop_first && !required && gave_back {
+                    result = self.stop(rest, pos, known, &collected)?;
+                }
+                result
+            }
+            Node::Capture { marker, inner } => {
+                let (marker, inner) = (*marker, *inner);
+                let start = Bindings::of(marker, &number!(pos as isize));
+                self.match_from(inner, pos, &known.clone().join(&start))?
+            }
+            Node::CaptureEnd { name, marker, rest } => {
+                let (name, marker, rest) = (*name, *marker, *rest);
+                let start = match known.get(marker) {
+                    Some(SExpr::Number(Number {
+                        rep: NumberRep::ArbitraryInteger(start),
+                        ..
+                    })) => start.to_usize().unwrap(),
+                    _ => unreachable!(
+                        "Reached the end of a capture that didn't start"
+                    ),
+                };
+                let known = Bindings::of_contents(
+                    known
+                        .iter()
+                        .filter(|(id, _)| **id != marker)
+                        .map(|(id, value)| (*id, value.clone()))
+                        .collect(),
+                );
+                let captured = Bindings::of(
+                    name,
+                    &SExpr::List(exprs[start..pos].to_vec()),
+                );
+                self.match_then(rest, pos, &known, &captured)?
+            }
+            Node::Acc {
+                acc,
+                init,
+                alternatives,
+            } => {
+                let mut bindings = init.clone();
+                let (acc, alternatives) = (acc.clone(), alternatives.clone());
+                for alternative in alternatives {
+                    let matched = self.match_from(alternative, pos, known)?;
+                    bindings = Option::<Bindings>::from_sexpr(patter_sr!(
+                        acc,
+                        SExpr::List(vec![
+                            bindings.into_sexpr(),
+                            matched.into_sexpr()
+                        ])
+                    )?)?;
+                }
+                bindings
+            }
+        };
+        self.memo
+            .entry((node, pos))
+            .or_default()
+            .push((known.clone(), result.clone()));
+        Ok(result)
+    }
+
+    /// Matches from `rest` at `pos` after a pattern bound `bindings`
+    fn match_then(
+        &mut self,
+        rest: NodeId,
+        pos: usize,
+        known: &Bindings,
+        bindings: &Bindings,
+    ) -> Result<Option<Bindings>, InterpreterError> {
+        Ok(match self.learn(known, bindings, &[]) {
+            Some(known) => self
+                .match_from(rest, pos, &known)?
+                .and_then(|r| r.unify(bindings)),
+            None => None,
+        })
+    }
+
+    /// `known` with the values `bindings` binds to repeated places, other than
+    /// those in `except`, added, unless they differ from those already known
+    fn learn(
+        &self,
+        known: &Bindings,
+        bindings: &Bindings,
+        except: &[Interned<'static, Ident>],
+    ) -> Option<Bindings> {
+        let learned = Bindings::of_contents(
+            bindings
+                .iter()
+                .filter(|(id, _)| {
+                    self.repeated.contains(id) && !except.contains(id)
+                })
+                .map(|(id, value)| (*id, value.clone()))
+                .collect(),
+        );
+        known.clone().unify(&learned)
+    }
+
+    /// The `iteration`th pattern of a Kleene pattern, generating it if it
+    /// hasn't been yet
+    fn pattern(
+        &mut self,
+        kleene: usize,
+        iteration: usize,
+    ) -> Result<SExpr, InterpreterError> {
+        let gen = &mut self.kleenes[kleene];
+        if gen.pats.len() == iteration {
+            let pat = gen.next.call(
+                vec![SExpr::List(gen.pats.clone())],
+                &mut Context::empty(),
+            )?;
+            gen.pats.push(pat);
+        }
+        Ok(gen.pats[iteration].clone())
+    }
+
+    /// The node for the repetition after that of a Kleene node
+    fn next(&mut self, node: NodeId) -> NodeId {
+        let (kleene, iteration, rest) = match self.nodes[node] {
+            Node::Kleene {
+                next: Some(next), ..
+            } => return next,
+            Node::Kleene {
+                kleene,
+                iteration,
+                rest,
+                ..
+            } => (kleene, iteration, rest),
+            _ => unreachable!(),
+        };
+        let next = self.push(Node::Kleene {
+            kleene,
+            iteration: iteration + 1,
+            rest,
+            next: None,
+        });
+        if let Node::Kleene { next: slot, .. } = &mut self.nodes[node] {
+            *slot = Some(next);
+        }
+        next
+    }
+
+    /// The places a Kleene pattern collects, which are those bound by both
+    /// of its first two repetitions
+    fn collected(
+        &mut self,
+        kleene: usize,
+    ) -> Result<Vec<Interned<'static, Ident>>, InterpreterError> {
+        if let Some(collected) = &self.kleenes[kleene].collected {
+            return Ok(collected.clone());
+        }
+        let first = self.pattern(kleene, 0)?.places();
+        let second = self.pattern(kleene, 1)?.places();
+        let mut collected = Vec::new();
+        for place in first {
+            if second.contains(&place)
+                && !collected.contains(&place)
+                && place != ident!("#/noread")
+            {
+                collected.push(place);
+            }
+        }
+        self.kleenes[kleene].collected = Some(collected.clone());
+        Ok(collected)
+    }
+
+    /// Matches the rest of the list after the last repetition of a Kleene
+    /// pattern, which collected nothing
+    fn stop(
+        &mut self,
+        rest: NodeId,
+        pos: usize,
+        known: &Bindings,
+        collected: &[Interned<'static, Ident>],
+    ) -> Result<Option<Bindings>, InterpreterError> {
+        let empty = Bindings::of_contents(
+            collected
+                .iter()
+                .map(|place| (*place, SExpr::List(vec![])))
+                .collect(),
+        );
+        Ok(self.match_from(rest, pos, known)?.map(|r| r.join(&empty)))
+    }
+
+    /// The ends and bindings of the ways `pat` can match by itself starting
+    /// at `pos`, longest first
+    fn repetitions(
+        &self,
+        pat: &SExpr,
+        pos: usize,
+    ) -> Result<Vec<(usize, Bindings)>, InterpreterError> {
+        if pat.matches_singular() {
+            return Ok(match self.exprs.get(pos) {
+                Some(expr) => pat
+                    .match_ptn(expr)?
+                    .map(|bindings| (pos + 1, bindings))
+                    .into_iter()
+                    .collect(),
+                None => vec![],
+            });
+        }
+        let mut repetitions = Vec::new();
+        for end in (pos..=self.exprs.len()).rev() {
+            let matched =
+                match_list(std::slice::from_ref(pat), &self.exprs[pos..end])?;
+            if let Some(bindings) = matched {
+                repetitions.push((end, bindings));
+            }
+        }
+        Ok(repetitions)
+    }
+}
+
+/// Adds the bindings of a repetition of a Kleene pattern to those of the
+/// repetitions after it and the rest of the list
+fn collect(
+    collected: &[Interned<'static, Ident>],
+    repetition: &Bindings,
+    rest: Bindings,
+) -> Option<Bindings> {
+    let mut bindings = rest;
+    let mut uncollected = Bindings::empty();
+    for (place, value) in repetition.iter() {
+        if collected.contains(place) {
+            let later = bindings
+                .get(*place)
+                .cloned()
+                .and_then(SExpr::as_list)
+                .unwrap_or_default();
+            let values = iter::once(value.clone()).chain(later).collect();
+            bindings.insert(Bindings::of(*place, &SExpr::List(values)));
+        } else {
+            uncollected.insert(Bindings::of(*place, value));
+        }
+    }
+    bindings.unify(&uncollected)
+}
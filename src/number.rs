@@ -1,10 +1,12 @@
-use num::bigint::{BigInt, BigUint, TryFromBigIntError};
+use num::bigint::{BigInt, BigUint, Sign, TryFromBigIntError};
 
 use num::rational::BigRational;
+use num::{Integer, One, Signed, ToPrimitive, Zero};
 
 use std::cmp::Ordering;
 use std::convert::TryFrom;
-use std::ops::Add;
+use std::fmt;
+use std::fmt::Display;
 
 #[derive(Clone, Debug)]
 pub struct Number {
@@ -23,6 +25,20 @@ pub enum NumberRep {
     },
 }
 
+impl Display for Number {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.rep {
+            NumberRep::ArbitraryInteger(i) => write!(f, "{}", i),
+            NumberRep::ArbitraryRational(r) => write!(f, "{}", r),
+            NumberRep::ArbitraryFloat {
+                significand,
+                exponent,
+                base,
+            } => write!(f, "{}*{}^{}", significand, base, exponent),
+        }
+    }
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub enum Precision {
     Rational {
@@ -58,13 +74,99 @@ pub enum PositiveIntegerOrInvPositiveInteger {
     Inv(BigUint),
 }
 
+impl<T> Range<T> {
+    pub fn new(lower: Bound<T>, upper: Bound<T>) -> Range<T> {
+        Range(LowerBound(lower), UpperBound(upper))
+    }
+}
+
 impl<T: PartialOrd> Range<T> {
-    fn subset_of(&self, other: &Range<T>) -> bool {
+    pub fn subset_of(&self, other: &Range<T>) -> bool {
         self.0 >= other.0 && self.1 <= other.1
     }
 
-    fn superset_of(&self, other: &Range<T>) -> bool {
-        self.0 <= other.0 && self.1 >= other.1
+    pub fn contains(&self, value: &T) -> bool {
+        use Bound::*;
+        let above_lower = match &(self.0).0 {
+            Inclusive(lower) => lower <= value,
+            Exclusive(lower) => lower < value,
+            Unbounded => true,
+        };
+        let below_upper = match &(self.1).0 {
+            Inclusive(upper) => value <= upper,
+            Exclusive(upper) => value < upper,
+            Unbounded => true,
+        };
+        above_lower && below_upper
+    }
+
+    /// Whether some value is in both ranges
+    pub fn intersects(&self, other: &Range<T>) -> bool {
+        use Bound::*;
+        let lower = if self.0 >= other.0 { &self.0 } else { &other.0 };
+        let upper = if self.1 <= other.1 { &self.1 } else { &other.1 };
+        match (&lower.0, &upper.0) {
+            (Unbounded, _) | (_, Unbounded) => true,
+            (Inclusive(lower), Inclusive(upper)) => lower <= upper,
+            (Inclusive(lower), Exclusive(upper))
+            | (Exclusive(lower), Inclusive(upper))
+            | (Exclusive(lower), Exclusive(upper)) => lower < upper,
+        }
+    }
+}
+
+impl<T: PartialOrd + Clone> Range<T> {
+    /// The values in both ranges, unless there are none
+    pub fn intersection(&self, other: &Range<T>) -> Option<Range<T>> {
+        let lower = if self.0 >= other.0 { &self.0 } else { &other.0 };
+        let upper = if self.1 <= other.1 { &self.1 } else { &other.1 };
+        let range = Range(lower.clone(), upper.clone());
+        range.intersects(&range).then_some(range)
+    }
+
+    /// The least range holding the values in both
+    pub fn hull(&self, other: &Range<T>) -> Range<T> {
+        let lower = if self.0 <= other.0 { &self.0 } else { &other.0 };
+        let upper = if self.1 >= other.1 { &self.1 } else { &other.1 };
+        Range(lower.clone(), upper.clone())
+    }
+
+    /// The values in this range but not `other`, as at most two ranges
+    pub fn minus(&self, other: &Range<T>) -> Vec<Range<T>> {
+        use Bound::*;
+        let below = match &(other.0).0 {
+            Inclusive(lower) => Some(Exclusive(lower.clone())),
+            Exclusive(lower) => Some(Inclusive(lower.clone())),
+            Unbounded => None,
+        }
+        .map(|upper| Range(LowerBound(Unbounded), UpperBound(upper)));
+        let above = match &(other.1).0 {
+            Inclusive(upper) => Some(Exclusive(upper.clone())),
+            Exclusive(upper) => Some(Inclusive(upper.clone())),
+            Unbounded => None,
+        }
+        .map(|lower| Range(LowerBound(lower), UpperBound(Unbounded)));
+        below
+            .into_iter()
+            .chain(above)
+            .filter_map(|part| part.intersection(self))
+            .collect()
+    }
+}
+
+impl<T: Display> Display for Range<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use Bound::*;
+        match &(self.0).0 {
+            Inclusive(lower) => write!(f, "[{}", lower)?,
+            Exclusive(lower) => write!(f, "({}", lower)?,
+            Unbounded => write!(f, "(..")?,
+        }
+        match &(self.1).0 {
+            Inclusive(upper) => write!(f, ", {}]", upper),
+            Exclusive(upper) => write!(f, ", {})", upper),
+            Unbounded => write!(f, ", ..)"),
+        }
     }
 }
 
@@ -136,19 +238,52 @@ impl PartialEq for Number {
     }
 }
 
-impl PartialEq for NumberRep {
-    fn eq(&self, other: &NumberRep) -> bool {
+impl PartialOrd for Number {
+    fn partial_cmp(&self, other: &Number) -> Option<Ordering> {
+        self.to_rational()?.partial_cmp(&other.to_rational()?)
+    }
+}
+
+impl Number {
+    /// The exact value of this number, which floats have too as their base
+    /// is an integer
+    pub fn to_rational(&self) -> Option<BigRational> {
+        self.rep.to_rational()
+    }
+}
+
+impl NumberRep {
+    /// The exact value of a number, unless it is a float whose exponent is
+    /// out of the range of an `i32` or which divides by a base of zero
+    fn to_rational(&self) -> Option<BigRational> {
         use NumberRep::*;
-        match (self, other) {
-            (ArbitraryInteger(left), ArbitraryInteger(right)) => left == right,
-            (ArbitraryRational(left), ArbitraryRational(right)) => {
-                left == right
+        match self {
+            ArbitraryInteger(int) => {
+                Some(BigRational::from_integer(int.clone()))
             }
-            (ArbitraryRational(rat), ArbitraryInteger(int))
-            | (ArbitraryInteger(int), ArbitraryRational(rat)) => {
-                *rat.denom() == BigInt::from(1) && rat.numer() == int
+            ArbitraryRational(rat) => Some(rat.clone()),
+            ArbitraryFloat {
+                significand,
+                exponent,
+                base,
+            } => {
+                let base =
+                    BigRational::from_integer(BigInt::from(base.clone()));
+                let exponent = exponent.to_i32()?;
+                if base.is_zero() && exponent < 0 {
+                    return None;
+                }
+                Some(significand.to_rational()? * base.pow(exponent))
             }
-            _ => unimplemented!(),
+        }
+    }
+}
+
+impl PartialEq for NumberRep {
+    fn eq(&self, other: &NumberRep) -> bool {
+        match (self.to_rational(), other.to_rational()) {
+            (Some(left), Some(right)) => left == right,
+            _ => false,
         }
     }
 }
@@ -175,41 +310,497 @@ impl Precision {
         }
     }
 
-    fn add(left: &Precision, right: &Precision) -> Precision {
-        use Bound::*;
+    /// The numerators and denominators of numbers of this precision. Those
+    /// of floats are their significands times the base to the greatest
+    /// exponent, over the base to the least, and anything smaller they
+    /// reduce to.
+    fn intervals(&self) -> (Interval, Interval) {
+        match self {
+            Precision::Rational {
+                numerator_range,
+                denominator_range,
+            } => (
+                Interval::of_range(numerator_range),
+                Interval::of_denominators(denominator_range),
+            ),
+            Precision::Float {
+                base,
+                significand_range,
+                exponent_range,
+            } => {
+                let exponents = Interval::of_range(exponent_range);
+                let base = Extended::Finite(BigInt::from(base.clone()));
+                let zero = Extended::Finite(BigInt::zero());
+                let one = Extended::Finite(BigInt::one());
+                let significands = Interval::of_significands(significand_range);
+                let significands = Interval {
+                    lower: significands.lower.min(zero.clone()),
+                    upper: significands.upper.max(zero.clone()),
+                };
+                (
+                    significands.mul(&Interval {
+                        lower: one.clone(),
+                        upper: base
+                            .pow(&exponents.upper.clone().max(zero.clone())),
+                    }),
+                    Interval {
+                        lower: one,
+                        upper: base.pow(&exponents.lower.neg().max(zero)),
+                    },
+                )
+            }
+        }
+    }
+
+    fn of_intervals(numerators: Interval, denominators: Interval) -> Precision {
+        Precision::Rational {
+            numerator_range: numerators.to_range(),
+            denominator_range: denominators.to_denominators(),
+        }
+    }
+
+    /// The base of a float precision
+    pub fn float_base(&self) -> Option<&BigUint> {
+        match self {
+            Precision::Float { base, .. } => Some(base),
+            Precision::Rational { .. } => None,
+        }
+    }
+
+    /// The rational precision holding every number this one does
+    pub fn rational(&self) -> Precision {
+        let (numerators, denominators) = self.intervals();
+        Precision::of_intervals(numerators, denominators)
+    }
+
+    /// The float precision the result of an operation on numbers of these
+    /// precisions is rounded to, if either is a float. Floats of the same
+    /// base are rounded to one with the ranges of both, while floats of
+    /// different bases give exact results, as neither base can hold what the
+    /// other does.
+    fn float_result(left: &Precision, right: &Precision) -> Option<Precision> {
         use Precision::*;
         match (left, right) {
             (
-                Rational {
-                    denominator_range: left_denominator_range,
-                    numerator_range:
-                        Range(
-                            LowerBound(Inclusive(left_lower)),
-                            UpperBound(Inclusive(left_upper)),
-                        ),
+                Float {
+                    base: left_base,
+                    significand_range: left_significands,
+                    exponent_range: left_exponents,
                 },
-                Rational {
-                    denominator_range: right_denominator_range,
-                    numerator_range:
-                        Range(
-                            LowerBound(Inclusive(right_lower)),
-                            UpperBound(Inclusive(right_upper)),
-                        ),
+                Float {
+                    base: right_base,
+                    significand_range: right_significands,
+                    exponent_range: right_exponents,
                 },
-            ) if left_denominator_range == right_denominator_range => {
-                Rational {
-                    denominator_range: left_denominator_range.clone(),
-                    numerator_range: Range(
-                        LowerBound(Inclusive(BigInt::from(
-                            left_lower + right_lower,
-                        ))),
-                        UpperBound(Inclusive(BigInt::from(
-                            left_upper + right_upper,
-                        ))),
+            ) => (left_base == right_base).then(|| Float {
+                base: left_base.clone(),
+                significand_range: left_significands.hull(right_significands),
+                exponent_range: left_exponents.hull(right_exponents),
+            }),
+            (float @ Float { .. }, _) | (_, float @ Float { .. }) => {
+                Some(float.clone())
+            }
+            _ => None,
+        }
+    }
+
+    /// The precision of the result of an operation on numbers of these
+    /// precisions: the float precision it is rounded to if there is one, or
+    /// else `rational` of their numerators and denominators
+    fn binary(
+        left: &Precision,
+        right: &Precision,
+        rational: impl FnOnce(
+            (Interval, Interval),
+            (Interval, Interval),
+        ) -> (Interval, Interval),
+    ) -> Precision {
+        Precision::float_result(left, right).unwrap_or_else(|| {
+            let (numerators, denominators) =
+                rational(left.intervals(), right.intervals());
+            Precision::of_intervals(numerators, denominators)
+        })
+    }
+
+    /// Like `binary`, for an operation on one number, which a float is
+    /// rounded back to the precision of
+    fn unary(
+        &self,
+        rational: impl FnOnce(Interval, Interval) -> (Interval, Interval),
+    ) -> Precision {
+        match self {
+            Precision::Float { .. } => self.clone(),
+            Precision::Rational { .. } => {
+                let (numerators, denominators) = self.intervals();
+                let (numerators, denominators) =
+                    rational(numerators, denominators);
+                Precision::of_intervals(numerators, denominators)
+            }
+        }
+    }
+
+    pub fn add(left: &Precision, right: &Precision) -> Precision {
+        Precision::binary(
+            left,
+            right,
+            |(left_num, left_den), (right_num, right_den)| {
+                (
+                    left_num.mul(&right_den).add(&right_num.mul(&left_den)),
+                    left_den.mul(&right_den),
+                )
+            },
+        )
+    }
+
+    pub fn sub(left: &Precision, right: &Precision) -> Precision {
+        Precision::add(left, &right.neg())
+    }
+
+    pub fn mul(left: &Precision, right: &Precision) -> Precision {
+        Precision::binary(
+            left,
+            right,
+            |(left_num, left_den), (right_num, right_den)| {
+                (left_num.mul(&right_num), left_den.mul(&right_den))
+            },
+        )
+    }
+
+    /// Moves the sign of the divisor's numerator to the numerator of the
+    /// quotient, as denominators are positive
+    pub fn div(left: &Precision, right: &Precision) -> Precision {
+        Precision::binary(
+            left,
+            right,
+            |(left_num, left_den), (right_num, right_den)| {
+                (
+                    left_num.mul(&right_den).mul(&right_num.signs()),
+                    left_den.mul(&right_num.abs()),
+                )
+            },
+        )
+    }
+
+    /// Of the integer parts of quotients, which are no bigger than the
+    /// quotients themselves
+    pub fn quot(left: &Precision, right: &Precision) -> Precision {
+        Precision::binary(left, right, |(left_num, _), (_, right_den)| {
+            (
+                Interval::symmetric(left_num.magnitude().mul(&right_den.upper)),
+                Interval::exactly(BigInt::one()),
+            )
+        })
+    }
+
+    /// Of remainders, which are smaller than the divisor and have a
+    /// denominator dividing the product of those of the operands
+    pub fn rem(left: &Precision, right: &Precision) -> Precision {
+        Precision::binary(
+            left,
+            right,
+            |(_, left_den), (right_num, right_den)| {
+                let denominators = left_den.mul(&right_den);
+                let bound = right_num.magnitude().mul(&denominators.upper);
+                (
+                    Interval::symmetric(
+                        bound.add(&Extended::Finite(-BigInt::one())),
                     ),
+                    denominators,
+                )
+            },
+        )
+    }
+
+    pub fn neg(&self) -> Precision {
+        self.unary(|numerators, denominators| (numerators.neg(), denominators))
+    }
+
+    pub fn abs(&self) -> Precision {
+        self.unary(|numerators, denominators| (numerators.abs(), denominators))
+    }
+
+    /// Of powers, whose numerators and denominators are each at most the
+    /// biggest numerator or denominator of the base to the biggest exponent
+    pub fn pow(base: &Precision, exponent: &Precision) -> Precision {
+        Precision::binary(
+            base,
+            exponent,
+            |(base_num, base_den), (exponent_num, _)| {
+                let most = base_num
+                    .magnitude()
+                    .max(base_den.upper)
+                    .max(Extended::Finite(BigInt::one()))
+                    .pow(&exponent_num.magnitude());
+                (
+                    Interval::symmetric(most.clone()),
+                    Interval {
+                        lower: Extended::Finite(BigInt::one()),
+                        upper: most,
+                    },
+                )
+            },
+        )
+    }
+}
+
+/// The most bits the bound of a power's precision is worked out to, past
+/// which it is taken to be unbounded
+const MAX_POW_PRECISION_BITS: u64 = 1 << 12;
+
+/// An integer or infinity, for the bounds of intervals
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Extended {
+    NegInfinity,
+    Finite(BigInt),
+    Infinity,
+}
+
+impl Extended {
+    fn neg(&self) -> Extended {
+        use Extended::*;
+        match self {
+            NegInfinity => Infinity,
+            Finite(n) => Finite(-n),
+            Infinity => NegInfinity,
+        }
+    }
+
+    /// The sum, taking infinities of opposite signs to cancel out to the
+    /// first, as only like bounds are added
+    fn add(&self, other: &Extended) -> Extended {
+        use Extended::*;
+        match (self, other) {
+            (Finite(a), Finite(b)) => Finite(a + b),
+            (Finite(_), infinity) => infinity.clone(),
+            (infinity, _) => infinity.clone(),
+        }
+    }
+
+    /// The product, taking zero times infinity to be zero, which is what it
+    /// is as a bound of a product of intervals
+    fn mul(&self, other: &Extended) -> Extended {
+        use Extended::*;
+        match (self, other) {
+            (Finite(a), Finite(b)) => Finite(a * b),
+            (Finite(n), infinity) | (infinity, Finite(n)) => match n.sign() {
+                Sign::NoSign => Finite(BigInt::zero()),
+                Sign::Plus => infinity.clone(),
+                Sign::Minus => infinity.neg(),
+            },
+            (a, b) if a == b => Infinity,
+            _ => NegInfinity,
+        }
+    }
+
+    fn abs(&self) -> Extended {
+        match self {
+            Extended::Finite(n) => Extended::Finite(n.abs()),
+            _ => Extended::Infinity,
+        }
+    }
+
+    /// This nonnegative bound to the power of another, or infinity if that
+    /// is too big to work out
+    fn pow(&self, exponent: &Extended) -> Extended {
+        use Extended::*;
+        match (self, exponent) {
+            (Finite(base), _) if base.is_one() => Finite(BigInt::one()),
+            (Finite(base), Finite(exponent))
+                if exponent
+                    .to_u64()
+                    .and_then(|e| e.checked_mul(base.bits()))
+                    .is_some_and(|bits| bits <= MAX_POW_PRECISION_BITS) =>
+            {
+                Finite(base.pow(exponent.to_u32().unwrap()))
+            }
+            _ => Infinity,
+        }
+    }
+}
+
+/// The integers between two bounds, which is how the numerators and
+/// denominators of a precision are worked out
+#[derive(Clone, Debug)]
+struct Interval {
+    lower: Extended,
+    upper: Extended,
+}
+
+impl Interval {
+    fn exactly(n: BigInt) -> Interval {
+        Interval {
+            lower: Extended::Finite(n.clone()),
+            upper: Extended::Finite(n),
+        }
+    }
+
+    /// The integers no further from zero than `magnitude`
+    fn symmetric(magnitude: Extended) -> Interval {
+        Interval {
+            lower: magnitude.neg(),
+            upper: magnitude,
+        }
+    }
+
+    fn of_range(range: &Range<BigInt>) -> Interval {
+        Interval::of_bounds(&(range.0).0, &(range.1).0, |n| Some(n.clone()))
+    }
+
+    /// Denominators are positive integers, so an inverse bound is taken to be
+    /// one below and unbounded above
+    fn of_denominators(
+        range: &Range<PositiveIntegerOrInvPositiveInteger>,
+    ) -> Interval {
+        let interval =
+            Interval::of_bounds(&(range.0).0, &(range.1).0, |d| match d {
+                PositiveIntegerOrInvPositiveInteger::Integer(d) => {
+                    Some(BigInt::from(d.clone()))
                 }
+                PositiveIntegerOrInvPositiveInteger::Inv(_) => None,
+            });
+        Interval {
+            lower: interval.lower.max(Extended::Finite(BigInt::one())),
+            upper: interval.upper,
+        }
+    }
+
+    /// Significands are integers, so bounds between them are rounded inward,
+    /// and bounds without exact values are taken to be unbounded
+    fn of_significands(range: &Range<Box<Number>>) -> Interval {
+        use Bound::*;
+        let lower = match &(range.0).0 {
+            Inclusive(n) => n.to_rational().map(|n| n.ceil().to_integer()),
+            Exclusive(n) => n.to_rational().map(|n| n.floor().to_integer() + 1),
+            Unbounded => None,
+        };
+        let upper = match &(range.1).0 {
+            Inclusive(n) => n.to_rational().map(|n| n.floor().to_integer()),
+            Exclusive(n) => n.to_rational().map(|n| n.ceil().to_integer() - 1),
+            Unbounded => None,
+        };
+        Interval {
+            lower: lower.map_or(Extended::NegInfinity, Extended::Finite),
+            upper: upper.map_or(Extended::Infinity, Extended::Finite),
+        }
+    }
+
+    fn of_bounds<T>(
+        lower: &Bound<T>,
+        upper: &Bound<T>,
+        int: impl Fn(&T) -> Option<BigInt>,
+    ) -> Interval {
+        use Bound::*;
+        let finite =
+            |n: Option<BigInt>, infinity| n.map_or(infinity, Extended::Finite);
+        Interval {
+            lower: match lower {
+                Inclusive(n) => finite(int(n), Extended::NegInfinity),
+                Exclusive(n) => {
+                    finite(int(n).map(|n| n + 1), Extended::NegInfinity)
+                }
+                Unbounded => Extended::NegInfinity,
+            },
+            upper: match upper {
+                Inclusive(n) => finite(int(n), Extended::Infinity),
+                Exclusive(n) => {
+                    finite(int(n).map(|n| n - 1), Extended::Infinity)
+                }
+                Unbounded => Extended::Infinity,
+            },
+        }
+    }
+
+    fn to_range(&self) -> Range<BigInt> {
+        let bound = |n: &Extended| match n {
+            Extended::Finite(n) => Bound::Inclusive(n.clone()),
+            _ => Bound::Unbounded,
+        };
+        Range::new(bound(&self.lower), bound(&self.upper))
+    }
+
+    fn to_denominators(&self) -> Range<PositiveIntegerOrInvPositiveInteger> {
+        let bound = |n: &Extended| match n {
+            Extended::Finite(n) => {
+                Bound::Inclusive(PositiveIntegerOrInvPositiveInteger::Integer(
+                    n.max(&BigInt::one()).to_biguint().unwrap(),
+                ))
             }
-            _ => unimplemented!(),
+            _ => Bound::Unbounded,
+        };
+        Range::new(bound(&self.lower), bound(&self.upper))
+    }
+
+    fn add(&self, other: &Interval) -> Interval {
+        Interval {
+            lower: self.lower.add(&other.lower),
+            upper: self.upper.add(&other.upper),
+        }
+    }
+
+    fn neg(&self) -> Interval {
+        Interval {
+            lower: self.upper.neg(),
+            upper: self.lower.neg(),
+        }
+    }
+
+    fn mul(&self, other: &Interval) -> Interval {
+        let products = [
+            self.lower.mul(&other.lower),
+            self.lower.mul(&other.upper),
+            self.upper.mul(&other.lower),
+            self.upper.mul(&other.upper),
+        ];
+        Interval {
+            lower: products.iter().min().unwrap().clone(),
+            upper: products.iter().max().unwrap().clone(),
+        }
+    }
+
+    fn abs(&self) -> Interval {
+        let zero = Extended::Finite(BigInt::zero());
+        if self.lower >= zero {
+            self.clone()
+        } else if self.upper <= zero {
+            self.neg()
+        } else {
+            Interval {
+                lower: zero,
+                upper: self.magnitude(),
+            }
+        }
+    }
+
+    /// Whether this holds every integer `other` does, or the other way round
+    fn inclusion(&self, other: &Interval) -> Option<Ordering> {
+        let within = self.lower >= other.lower && self.upper <= other.upper;
+        let around = self.lower <= other.lower && self.upper >= other.upper;
+        match (within, around) {
+            (true, true) => Some(Ordering::Equal),
+            (true, false) => Some(Ordering::Less),
+            (false, true) => Some(Ordering::Greater),
+            (false, false) => None,
+        }
+    }
+
+    /// The furthest from zero any of the integers is
+    fn magnitude(&self) -> Extended {
+        self.lower.abs().max(self.upper.abs())
+    }
+
+    /// The signs of the integers, as -1 and 1
+    fn signs(&self) -> Interval {
+        let zero = Extended::Finite(BigInt::zero());
+        let sign = |negative| {
+            Extended::Finite(if negative {
+                -BigInt::one()
+            } else {
+                BigInt::one()
+            })
+        };
+        Interval {
+            lower: sign(self.lower < zero),
+            upper: sign(self.upper <= zero),
         }
     }
 }
@@ -225,7 +816,7 @@ impl From<isize> for Number {
 
 #[derive(Debug)]
 pub enum FromNumberError {
-    PrecisionTooLow,
+    NotAnInteger,
     OutOfBounds,
     TryFromBigIntError(TryFromBigIntError<BigInt>),
 }
@@ -239,67 +830,459 @@ impl From<TryFromBigIntError<BigInt>> for FromNumberError {
 impl TryFrom<Number> for usize {
     type Error = FromNumberError;
 
+    /// Goes by the value rather than the precision, as a number whose
+    /// precision reaches past the range of a `usize` may still hold one
     fn try_from(num: Number) -> Result<usize, FromNumberError> {
-        if num.precision
-            < Precision::integer(usize::MIN.into(), usize::MAX.into())
-        {
-            return Err(FromNumberError::PrecisionTooLow);
+        let value = num.to_rational().ok_or(FromNumberError::OutOfBounds)?;
+        if !value.is_integer() {
+            return Err(FromNumberError::NotAnInteger);
         }
-        Ok(match num.rep {
-            NumberRep::ArbitraryInteger(i) => usize::try_from(i)?,
-            _ => unimplemented!(),
-        })
+        Ok(usize::try_from(value.to_integer())?)
     }
 }
 
-impl Add<Number> for Number {
-    type Output = Number;
+impl Number {
+    /// A number of the given value, held as an integer if it is one
+    fn of_rational(value: BigRational, precision: Precision) -> Number {
+        let rep = if value.is_integer() {
+            NumberRep::ArbitraryInteger(value.to_integer())
+        } else {
+            NumberRep::ArbitraryRational(value)
+        };
+        Number { rep, precision }
+    }
 
-    fn add(self, other: Number) -> Number {
-        use NumberRep::*;
-        match (self.rep, other.rep) {
-            (ArbitraryInteger(left), ArbitraryInteger(right)) => Number {
-                rep: ArbitraryInteger(left + right),
-                precision: Precision::add(&self.precision, &other.precision),
-            },
-            _ => unimplemented!(),
+    /// Applies `op` to the values of this number and `other` and `precision`
+    /// to their precisions. If either is a float, the result is rounded in
+    /// the direction `rounding` to the float precision `precision` gives.
+    fn rational_op(
+        &self,
+        other: &Number,
+        op: impl FnOnce(BigRational, BigRational) -> Option<BigRational>,
+        precision: fn(&Precision, &Precision) -> Precision,
+        rounding: Rounding,
+    ) -> Option<Number> {
+        let value = op(self.to_rational()?, other.to_rational()?)?;
+        match precision(&self.precision, &other.precision) {
+            float @ Precision::Float { .. } => {
+                Number::round_to_float(&value, &float, rounding)
+            }
+            precision => Some(Number::of_rational(value, precision)),
+        }
+    }
+
+    /// Applies `op` to the value of this number and `precision` to its
+    /// precision, rounding the result to nearest if it is a float
+    fn rational_op_unary(
+        &self,
+        op: impl FnOnce(BigRational) -> BigRational,
+        precision: fn(&Precision) -> Precision,
+    ) -> Option<Number> {
+        let value = op(self.to_rational()?);
+        match precision(&self.precision) {
+            float @ Precision::Float { .. } => {
+                Number::round_to_float(&value, &float, Rounding::NearestEven)
+            }
+            precision => Some(Number::of_rational(value, precision)),
         }
     }
+
+    pub fn checked_add(&self, other: &Number) -> Option<Number> {
+        self.add_rounded(other, Rounding::NearestEven)
+    }
+
+    /// The sum, rounded in the direction `rounding` if it is a float
+    pub fn add_rounded(
+        &self,
+        other: &Number,
+        rounding: Rounding,
+    ) -> Option<Number> {
+        self.rational_op(other, |a, b| Some(a + b), Precision::add, rounding)
+    }
+
+    pub fn checked_sub(&self, other: &Number) -> Option<Number> {
+        self.sub_rounded(other, Rounding::NearestEven)
+    }
+
+    /// The difference, rounded in the direction `rounding` if it is a float
+    pub fn sub_rounded(
+        &self,
+        other: &Number,
+        rounding: Rounding,
+    ) -> Option<Number> {
+        self.rational_op(other, |a, b| Some(a - b), Precision::sub, rounding)
+    }
+
+    pub fn checked_mul(&self, other: &Number) -> Option<Number> {
+        self.mul_rounded(other, Rounding::NearestEven)
+    }
+
+    /// The product, rounded in the direction `rounding` if it is a float
+    pub fn mul_rounded(
+        &self,
+        other: &Number,
+        rounding: Rounding,
+    ) -> Option<Number> {
+        self.rational_op(other, |a, b| Some(a * b), Precision::mul, rounding)
+    }
+
+    /// The exact quotient, which is rational unless `other` divides this
+    pub fn checked_div(&self, other: &Number) -> Option<Number> {
+        self.div_rounded(other, Rounding::NearestEven)
+    }
+
+    /// The quotient, rounded in the direction `rounding` if it is a float
+    pub fn div_rounded(
+        &self,
+        other: &Number,
+        rounding: Rounding,
+    ) -> Option<Number> {
+        self.rational_op(
+            other,
+            |a, b| (!b.is_zero()).then(|| a / b),
+            Precision::div,
+            rounding,
+        )
+    }
+
+    /// The quotient rounded toward zero
+    pub fn checked_quot(&self, other: &Number) -> Option<Number> {
+        self.rational_op(
+            other,
+            |a, b| (!b.is_zero()).then(|| (a / b).trunc()),
+            Precision::quot,
+            Rounding::NearestEven,
+        )
+    }
+
+    /// The remainder of `checked_quot`, which has the sign of this number
+    pub fn checked_rem(&self, other: &Number) -> Option<Number> {
+        self.rational_op(
+            other,
+            |a, b| (!b.is_zero()).then(|| &a - &b * (&a / &b).trunc()),
+            Precision::rem,
+            Rounding::NearestEven,
+        )
+    }
+
+    /// The remainder of the quotient rounded down, which has the sign of
+    /// `other`
+    pub fn checked_mod(&self, other: &Number) -> Option<Number> {
+        self.rational_op(
+            other,
+            |a, b| (!b.is_zero()).then(|| &a - &b * (&a / &b).floor()),
+            Precision::rem,
+            Rounding::NearestEven,
+        )
+    }
+
+    /// This number to an integer power
+    pub fn checked_pow(&self, exponent: &Number) -> Option<Number> {
+        self.rational_op(
+            exponent,
+            |a, b| {
+                let b = b.is_integer().then(|| b.to_integer())?.to_i32()?;
+                (b >= 0 || !a.is_zero()).then(|| a.pow(b))
+            },
+            Precision::pow,
+            Rounding::NearestEven,
+        )
+    }
+
+    pub fn checked_neg(&self) -> Option<Number> {
+        self.rational_op_unary(|a| -a, Precision::neg)
+    }
+
+    pub fn checked_abs(&self) -> Option<Number> {
+        self.rational_op_unary(|a| a.abs(), Precision::abs)
+    }
 }
 
+/// Precisions are ordered by whether one holds every number the other does,
+/// going by the ranges of their parts: numerators and denominators, or the
+/// significands and exponents of floats of the same base
 impl PartialOrd for Precision {
     fn partial_cmp(&self, other: &Precision) -> Option<Ordering> {
         use Precision::*;
+        let both = |left: Option<Ordering>, right: Option<Ordering>| match (
+            left?, right?,
+        ) {
+            (Ordering::Equal, ordering) | (ordering, Ordering::Equal) => {
+                Some(ordering)
+            }
+            (left, right) => (left == right).then_some(left),
+        };
         match (self, other) {
+            (Rational { .. }, Rational { .. }) => {
+                let (left_num, left_den) = self.intervals();
+                let (right_num, right_den) = other.intervals();
+                both(
+                    left_num.inclusion(&right_num),
+                    left_den.inclusion(&right_den),
+                )
+            }
             (
-                Rational {
-                    denominator_range: left_denominator_range,
-                    numerator_range: left_numerator_range,
+                Float {
+                    base: left_base,
+                    significand_range: left_significands,
+                    exponent_range: left_exponents,
                 },
-                Rational {
-                    denominator_range: right_denominator_range,
-                    numerator_range: right_numerator_range,
+                Float {
+                    base: right_base,
+                    significand_range: right_significands,
+                    exponent_range: right_exponents,
                 },
-            ) => {
-                if left_denominator_range == right_denominator_range {
-                    if left_numerator_range == right_numerator_range {
-                        Some(Ordering::Equal)
-                    } else if left_numerator_range
-                        .subset_of(right_numerator_range)
-                    {
-                        Some(Ordering::Less)
-                    } else if left_numerator_range
-                        .superset_of(right_numerator_range)
-                    {
-                        Some(Ordering::Greater)
+            ) if left_base == right_base => both(
+                Interval::of_significands(left_significands)
+                    .inclusion(&Interval::of_significands(right_significands)),
+                Interval::of_range(left_exponents)
+                    .inclusion(&Interval::of_range(right_exponents)),
+            ),
+            _ => None,
+        }
+    }
+}
+
+/// How a value between two floats is rounded to one of them
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Rounding {
+    /// To the nearer one, or the one with an even significand if neither is
+    NearestEven,
+    TowardZero,
+    /// To the greater one
+    Up,
+    /// To the lesser one
+    Down,
+}
+
+impl Rounding {
+    fn round(self, value: &BigRational) -> BigInt {
+        match self {
+            Rounding::NearestEven => {
+                let floor = value.floor();
+                let half = BigRational::new(BigInt::one(), BigInt::from(2));
+                let above = value - &floor;
+                let floor = floor.to_integer();
+                match above.cmp(&half) {
+                    Ordering::Less => floor,
+                    Ordering::Greater => floor + 1,
+                    Ordering::Equal if floor.is_even() => floor,
+                    Ordering::Equal => floor + 1,
+                }
+            }
+            Rounding::TowardZero => value.trunc().to_integer(),
+            Rounding::Up => value.ceil().to_integer(),
+            Rounding::Down => value.floor().to_integer(),
+        }
+    }
+}
+
+impl<T> Range<T> {
+    /// This range with `f` applied to its bounds, unless it fails for one
+    fn try_map<U>(&self, f: impl Fn(&T) -> Option<U>) -> Option<Range<U>> {
+        use Bound::*;
+        let bound = |bound: &Bound<T>| {
+            Some(match bound {
+                Inclusive(n) => Inclusive(f(n)?),
+                Exclusive(n) => Exclusive(f(n)?),
+                Unbounded => Unbounded,
+            })
+        };
+        Some(Range::new(bound(&(self.0).0)?, bound(&(self.1).0)?))
+    }
+}
+
+impl Precision {
+    /// The precision of floats of significands in one range times `base` to
+    /// the power of exponents in another, unless the base is less than two or
+    /// the exponents aren't integers
+    pub fn float(
+        base: BigUint,
+        significands: &Range<Number>,
+        exponents: &Range<Number>,
+    ) -> Option<Precision> {
+        (base >= BigUint::from(2_u32)).then_some(())?;
+        Some(Precision::Float {
+            base,
+            significand_range: significands
+                .try_map(|n| Some(Box::new(n.clone())))?,
+            exponent_range: exponents.try_map(|n| {
+                let n = n.to_rational()?;
+                n.is_integer().then(|| n.to_integer())
+            })?,
+        })
+    }
+
+    /// The precision of an `f64`, which holds subnormal numbers as well
+    pub fn f64() -> Precision {
+        let most = Number::from((1_isize << 53) - 1);
+        Precision::Float {
+            base: BigUint::from(2_u32),
+            significand_range: Range::new(
+                Bound::Inclusive(Box::new(most.checked_neg().unwrap())),
+                Bound::Inclusive(Box::new(most)),
+            ),
+            exponent_range: Range::new(
+                Bound::Inclusive(BigInt::from(-1074)),
+                Bound::Inclusive(BigInt::from(971)),
+            ),
+        }
+    }
+
+    /// The least and greatest significands of a float precision, unless
+    /// either is unbounded
+    fn significands(&self) -> Option<(BigInt, BigInt)> {
+        match self {
+            Precision::Float {
+                significand_range, ..
+            } => match Interval::of_significands(significand_range) {
+                Interval {
+                    lower: Extended::Finite(least),
+                    upper: Extended::Finite(most),
+                } => Some((least, most)),
+                _ => None,
+            },
+            Precision::Rational { .. } => None,
+        }
+    }
+}
+
+impl Number {
+    /// The float of `precision` that `value` rounds to in the direction
+    /// `rounding`, which has the least exponent it can. Fails if `value` is
+    /// out of range, or the significands of `precision` are unbounded.
+    fn round_to_float(
+        value: &BigRational,
+        precision: &Precision,
+        rounding: Rounding,
+    ) -> Option<Number> {
+        let (base, exponents) = match precision {
+            Precision::Float {
+                base,
+                exponent_range,
+                ..
+            } => (
+                BigInt::from(base.clone()),
+                Interval::of_range(exponent_range),
+            ),
+            Precision::Rational { .. } => return None,
+        };
+        let (least, most) = precision.significands()?;
+        let scaled = |exponent: &BigInt| {
+            let scale =
+                BigRational::from_integer(base.clone()).pow(exponent.to_i32()?);
+            Some(rounding.round(&(value / scale)))
+        };
+        let clamp = |exponent: BigInt| match Extended::Finite(exponent)
+            .max(exponents.lower.clone())
+            .min(exponents.upper.clone())
+        {
+            Extended::Finite(exponent) => Some(exponent),
+            _ => None,
+        };
+        let exponent = if value.is_zero() {
+            clamp(BigInt::zero())?
+        } else {
+            // Significands of the other sign are never big enough
+            let bound = if value.is_positive() { &most } else { &least };
+            if bound.is_zero() || bound.is_positive() != value.is_positive() {
+                return None;
+            }
+            let bits = |n: &BigInt| n.bits() as f64;
+            let estimate =
+                (bits(value.numer()) - bits(value.denom()) - bits(bound))
+                    / base.to_f64()?.log2();
+            let too_big = |exponent: &BigInt| {
+                scaled(exponent).map(|s| {
+                    if value.is_positive() {
+                        s > most
                     } else {
-                        None
+                        s < least
                     }
-                } else {
-                    unimplemented!()
+                })
+            };
+            let mut exponent = clamp(BigInt::from(estimate.floor() as i64))?;
+            while too_big(&exponent)? {
+                if Extended::Finite(exponent.clone()) >= exponents.upper {
+                    return None;
                 }
+                exponent += 1;
+            }
+            while Extended::Finite(exponent.clone()) > exponents.lower
+                && !too_big(&(&exponent - 1))?
+            {
+                exponent -= 1;
+            }
+            exponent
+        };
+        let significand = scaled(&exponent)?;
+        // Out of range when zero isn't a significand, or a value too small
+        // for the least exponent rounds to zero
+        if significand < least || significand > most {
+            return None;
+        }
+        Some(Number {
+            rep: NumberRep::ArbitraryFloat {
+                significand: Box::new(Number {
+                    rep: NumberRep::ArbitraryInteger(significand),
+                    precision: Precision::integer(least, most),
+                }),
+                exponent,
+                base: base.to_biguint().unwrap(),
+            },
+            precision: precision.clone(),
+        })
+    }
+
+    /// This number rounded to a float of `precision` in the direction
+    /// `rounding`
+    pub fn to_float(
+        &self,
+        precision: &Precision,
+        rounding: Rounding,
+    ) -> Option<Number> {
+        Number::round_to_float(&self.to_rational()?, precision, rounding)
+    }
+
+    /// The exact value of a float as a rational, or of any other number
+    pub fn to_exact(&self) -> Option<Number> {
+        match &self.precision {
+            Precision::Float { .. } => Some(Number::of_rational(
+                self.to_rational()?,
+                self.precision.rational(),
+            )),
+            Precision::Rational { .. } => Some(self.clone()),
+        }
+    }
+
+    /// The exact value of a finite `f64`
+    pub fn from_f64(float: f64) -> Option<Number> {
+        Number::round_to_float(
+            &BigRational::from_float(float)?,
+            &Precision::f64(),
+            Rounding::NearestEven,
+        )
+    }
+
+    /// The nearest `f64`, unless this number is too big for one
+    pub fn to_f64(&self) -> Option<f64> {
+        let float = self.to_float(&Precision::f64(), Rounding::NearestEven)?;
+        match float.rep {
+            NumberRep::ArbitraryFloat {
+                significand,
+                exponent,
+                ..
+            } => {
+                // In two steps, as the least exponent is out of range of an
+                // f64 when negated
+                let exponent = exponent.to_i32()?;
+                let half = exponent / 2;
+                Some(
+                    significand.to_rational()?.to_integer().to_f64()?
+                        * 2_f64.powi(half)
+                        * 2_f64.powi(exponent - half),
+                )
             }
-            (Float { .. }, Float { .. }) => unimplemented!(),
             _ => None,
         }
     }
@@ -65,6 +65,10 @@ pub fn lex(source: &str) -> Vec<Token> {
                     &mut offset,
                 )));
             }
+            '.' if source[*offset..].starts_with("..") => {
+                inc_char_idx(source, &mut offset);
+                tokens.push(Word("..".to_string()));
+            }
             '/' => tokens.push(NSOperator),
             s if is_sigil_char(s) => {
                 tokens.push(Sigil(s));
@@ -0,0 +1,394 @@
+//! Regex-like patterns over strings, which are lists of graphemes. A regex is
+//! compiled into the list patterns that would otherwise be written by hand:
+//! sequences become consecutive patterns, alternatives unions, repetitions
+//! Kleene patterns and named groups captures.
+//!
+//! A regex matches a whole string. It can contain literal graphemes, `.` for
+//! any grapheme, classes like `[a-z_]` and `[^,]`, the escapes `\d`, `\w` and
+//! `\s` and their negations `\D`, `\W` and `\S`, groups `(...)`, `(?:...)`
+//! and `(?<name>...)`, alternatives separated by `|`, and the quantifiers
+//! `*`, `+`, `?`, `{n}`, `{n,}` and `{n,m}`, which are lazy when followed by
+//! `?` and possessive when followed by `+`. Anything else is matched
+//! literally after a `\`.
+
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::context::{Bindings, Context};
+use crate::error::InterpreterError;
+use crate::number::{self, Number};
+use crate::parse;
+use crate::{Fun, IntoSExpr, KleeneMode, SExpr};
+
+/// Compiles a regex into a pattern matching the strings it does, binding the
+/// strings its named groups match to places of the same name
+pub fn compile(source: &str) -> Result<SExpr, InterpreterError> {
+    let mut parser = Parser {
+        source,
+        graphemes: source.graphemes(true).collect(),
+        pos: 0,
+    };
+    let ptn = parser.alternatives()?;
+    match parser.peek() {
+        None => Ok(SExpr::List(vec![ptn])),
+        Some(_) => Err(parser.error("Unmatched ) in regex")),
+    }
+}
+
+struct Parser<'a> {
+    source: &'a str,
+    graphemes: Vec<&'a str>,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&'a str> {
+        self.graphemes.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<&'a str> {
+        let grapheme = self.peek();
+        self.pos += 1;
+        grapheme
+    }
+
+    fn eat(&mut self, expected: &str) -> bool {
+        let found = self.peek() == Some(expected);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn expect(&mut self, expected: &str) -> Result<(), InterpreterError> {
+        if self.eat(expected) {
+            Ok(())
+        } else {
+            Err(self.error("Unexpected end of regex"))
+        }
+    }
+
+    fn error(&self, msg: &'static str) -> InterpreterError {
+        interpreter_err!(
+            CannotConvert,
+            msg,
+            self.source.to_string().into_sexpr()
+        )
+    }
+
+    /// Sequences separated by `|`
+    fn alternatives(&mut self) -> Result<SExpr, InterpreterError> {
+        let mut alternatives = vec![self.sequence()?];
+        while self.eat("|") {
+            alternatives.push(self.sequence()?);
+        }
+        // Not `SExpr::union`, which gives up on alternatives like `.` that
+        // are places
+        Ok(match alternatives.len() {
+            1 => alternatives.pop().unwrap(),
+            _ => SExpr::PtnAcc {
+                acc: Fun::union_acc(),
+                init: None,
+                pats: alternatives,
+            },
+        })
+    }
+
+    fn sequence(&mut self) -> Result<SExpr, InterpreterError> {
+        let mut items = Vec::new();
+        while !matches!(self.peek(), None | Some("|") | Some(")")) {
+            items.push(self.repetition()?);
+        }
+        Ok(match items.len() {
+            1 => items.pop().unwrap(),
+            _ => SExpr::Consecutive(items),
+        })
+    }
+
+    /// An atom, followed by any number of quantifiers
+    fn repetition(&mut self) -> Result<SExpr, InterpreterError> {
+        let mut ptn = self.atom()?;
+        while let Some((min, max)) = self.quantifier()? {
+            let mode = if self.eat("?") {
+                KleeneMode::Lazy
+            } else if self.eat("+") {
+                KleeneMode::Possessive
+            } else {
+                KleeneMode::Greedy
+            };
+            ptn = repeat(ptn, min, max, mode);
+        }
+        Ok(ptn)
+    }
+
+    /// The least and, if there is one, the most repetitions a quantifier
+    /// allows
+    fn quantifier(
+        &mut self,
+    ) -> Result<Option<(usize, Option<usize>)>, InterpreterError> {
+        let quantifier = match self.peek() {
+            Some("*") => (0, None),
+            Some("+") => (1, None),
+            Some("?") => (0, Some(1)),
+            Some("{") => return self.counted().map(Some),
+            _ => return Ok(None),
+        };
+        self.pos += 1;
+        Ok(Some(quantifier))
+    }
+
+    /// `{n}`, `{n,}` or `{n,m}`
+    fn counted(&mut self) -> Result<(usize, Option<usize>), InterpreterError> {
+        self.expect("{")?;
+        let min = self.count()?;
+        let max = if self.eat(",") {
+            match self.peek() {
+                Some("}") => None,
+                _ => Some(self.count()?),
+            }
+        } else {
+            Some(min)
+        };
+        self.expect("}")?;
+        if max.is_some_and(|max| max < min) {
+            return Err(self.error("Repetition range out of order"));
+        }
+        Ok((min, max))
+    }
+
+    fn count(&mut self) -> Result<usize, InterpreterError> {
+        let mut digits = String::new();
+        while let Some(digit) = self.peek().filter(|g| is_digit(g)) {
+            digits.push_str(digit);
+            self.pos += 1;
+        }
+        digits
+            .parse()
+            .map_err(|_| self.error("Expected a number of repetitions"))
+    }
+
+    fn atom(&mut self) -> Result<SExpr, InterpreterError> {
+        match self.next() {
+            Some("(") => self.group(),
+            Some("[") => self.class(),
+            Some(".") => Ok(SExpr::any()),
+            Some("\\") => match self.next() {
+                Some(escape) => Ok(match class_escape(escape) {
+                    Some(code_points) => with_first(code_points),
+                    None => literal(escape),
+                }),
+                None => Err(self.error("Unexpected end of regex")),
+            },
+            Some("*") | Some("+") | Some("?") | Some("{") => {
+                Err(self.error("Nothing to repeat"))
+            }
+            Some(grapheme) => Ok(literal(grapheme)),
+            None => Err(self.error("Unexpected end of regex")),
+        }
+    }
+
+    /// The rest of a group, after its `(`
+    fn group(&mut self) -> Result<SExpr, InterpreterError> {
+        let name = if self.eat("?") {
+            if self.eat(":") {
+                None
+            } else {
+                self.expect("<")?;
+                let mut name = String::new();
+                while let Some(c) = self.peek().filter(|g| is_name(g)) {
+                    name.push_str(c);
+                    self.pos += 1;
+                }
+                self.expect(">")?;
+                if name.is_empty() {
+                    return Err(self.error("Groups need a name after ?"));
+                }
+                if !name.as_bytes()[0].is_ascii_alphabetic() {
+                    return Err(self.error("Group names start with a letter"));
+                }
+                Some(crate::IDENTS.intern(crate::Ident {
+                    names: vec![name],
+                    tl_ns: false,
+                }))
+            }
+        } else {
+            None
+        };
+        let ptn = self.alternatives()?;
+        if !self.eat(")") {
+            return Err(self.error("Unclosed ( in regex"));
+        }
+        Ok(match name {
+            Some(name) => SExpr::Capture {
+                name,
+                ptn: Box::new(ptn),
+            },
+            None => ptn,
+        })
+    }
+
+    /// The rest of a class, after its `[`. A `]` right at the start is part
+    /// of the class.
+    fn class(&mut self) -> Result<SExpr, InterpreterError> {
+        let negated = self.eat("^");
+        let mut members = Vec::new();
+        let mut first = true;
+        loop {
+            let member = match self.next() {
+                None => return Err(self.error("Unclosed [ in regex")),
+                Some("]") if !first => break,
+                Some("\\") => match self.next() {
+                    Some(escape) => match class_escape(escape) {
+                        Some(code_points) => {
+                            members.push(code_points);
+                            first = false;
+                            continue;
+                        }
+                        None => escape,
+                    },
+                    None => return Err(self.error("Unclosed [ in regex")),
+                },
+                Some(member) => member,
+            };
+            first = false;
+            let lower = self.code_point(member)?;
+            let is_range = self.peek() == Some("-")
+                && !matches!(
+                    self.graphemes.get(self.pos + 1),
+                    None | Some(&"]")
+                );
+            if is_range {
+                self.pos += 1;
+                let upper = match self.next() {
+                    Some("\\") => self.next().unwrap_or_default(),
+                    upper => upper.unwrap_or_default(),
+                };
+                let upper = self.code_point(upper)?;
+                if upper < lower {
+                    return Err(self.error("Class range out of order"));
+                }
+                members.push(range(lower, upper));
+            } else {
+                members.push(lower.into_sexpr());
+            }
+        }
+        let code_points = SExpr::union(members);
+        Ok(with_first(if negated {
+            SExpr::Not(Box::new(code_points))
+        } else {
+            code_points
+        }))
+    }
+
+    /// The single code point of a grapheme in a class
+    fn code_point(&self, grapheme: &str) -> Result<char, InterpreterError> {
+        let mut chars = grapheme.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => Ok(c),
+            _ => Err(self.error("Classes can only hold single code points")),
+        }
+    }
+}
+
+/// Matches the grapheme, as strings hold it
+fn literal(grapheme: &str) -> SExpr {
+    let mut parts = grapheme
+        .chars()
+        .map(IntoSExpr::into_sexpr)
+        .collect::<Vec<_>>();
+    parts.push(SExpr::ZeroWidth(Box::new(SExpr::Ident(ident!(
+        "extended-grapheme-cluster"
+    )))));
+    SExpr::List(parts)
+}
+
+/// Matches the graphemes whose first code point `code_points` matches
+fn with_first(code_points: SExpr) -> SExpr {
+    SExpr::List(vec![
+        code_points,
+        repeat(SExpr::any(), 0, None, KleeneMode::Greedy),
+    ])
+}
+
+/// The code points of an escape like `\d`, if it is one
+fn class_escape(escape: &str) -> Option<SExpr> {
+    let members = match escape.to_lowercase().as_str() {
+        "d" => vec![range('0', '9')],
+        "w" => vec![
+            range('a', 'z'),
+            range('A', 'Z'),
+            range('0', '9'),
+            '_'.into_sexpr(),
+        ],
+        "s" => vec![' '.into_sexpr(), range('\t', '\r')],
+        _ => return None,
+    };
+    let code_points = SExpr::union(members);
+    Some(if escape.chars().all(char::is_uppercase) {
+        SExpr::Not(Box::new(code_points))
+    } else {
+        code_points
+    })
+}
+
+fn range(lower: char, upper: char) -> SExpr {
+    let bound = |c: char| match c.into_sexpr() {
+        SExpr::Number(n) => number::Bound::Inclusive(n),
+        _ => unreachable!(),
+    };
+    SExpr::Range(Box::new(number::Range::new(bound(lower), bound(upper))))
+}
+
+fn is_digit(grapheme: &str) -> bool {
+    grapheme.len() == 1 && grapheme.as_bytes()[0].is_ascii_digit()
+}
+
+fn is_name(grapheme: &str) -> bool {
+    grapheme.len() == 1
+        && (grapheme.as_bytes()[0].is_ascii_alphanumeric() || grapheme == "-")
+}
+
+/// A Kleene pattern of between `min` and `max` repetitions of `ptn`
+fn repeat(
+    ptn: SExpr,
+    min: usize,
+    max: Option<usize>,
+    mode: KleeneMode,
+) -> SExpr {
+    let max = match max {
+        Some(max) => number!(max as isize),
+        None => SExpr::UnarySigilApp(
+            ':',
+            Box::new(SExpr::Ident(ident!("unbounded"))),
+        ),
+    };
+    SExpr::Kleene {
+        start: Box::new(SExpr::List(vec![ptn.clone(); min])),
+        next: Fun {
+            body: Box::new(SExpr::Operation {
+                eval: next_repetition,
+                evals_to: |_| SExpr::any(),
+            }),
+            args_ptn: Box::new(SExpr::List(vec![SExpr::Place(ident!("prev"))])),
+            closure: Box::new(
+                Bindings::of(ident!("ptn"), &ptn)
+                    .join(&Bindings::of(ident!("max"), &max)),
+            ),
+            next_clause: None,
+            id: crate::Fun::fresh_id(),
+            extensions: crate::Extensions::default(),
+        },
+        mode,
+    }
+}
+
+/// The pattern of the repetition after `prev`, which matches nothing once
+/// there have been `max` of them, unless `max` is `:unbounded`
+fn next_repetition(cxt: &mut Context) -> Result<SExpr, InterpreterError> {
+    let taken = get!("prev", cxt).as_list().unwrap_or_default().len();
+    Ok(match get!("max", cxt).as_number() {
+        Some(max) if Number::from(taken as isize) >= max => {
+            SExpr::union(vec![])
+        }
+        _ => get!("ptn", cxt),
+    })
+}
@@ -0,0 +1,178 @@
+//! Rewriting a tree with `[pattern template]` rules. Wherever the pattern of
+//! a rule matches, the subtree is replaced with the template, quoted code
+//! evaluated with the bindings of the match. Passes over the tree are made
+//! until one changes nothing, so rules should stop changing what they
+//! produce. Rewriting gives up after `MAX_REWRITES` changes.
+
+use crate::context::Context;
+use crate::error::InterpreterError;
+use crate::parse;
+use crate::{FromSExpr, SExpr, SExprKind};
+
+/// How many subtrees can be changed before rewriting is taken not to
+/// terminate
+const MAX_REWRITES: usize = 1_000;
+
+/// Where rules are tried in a pass over a tree
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Order {
+    /// Before the children of each subtree, so what a rule produces is
+    /// rewritten in the same pass
+    TopDown,
+    /// After the children of each subtree
+    BottomUp,
+}
+
+impl FromSExpr for Order {
+    fn from_sexpr(expr: SExpr) -> Result<Order, InterpreterError> {
+        let keyword = |name| {
+            SExpr::UnarySigilApp(':', Box::new(SExpr::Ident(ident!(name))))
+        };
+        if expr == keyword("top-down") {
+            Ok(Order::TopDown)
+        } else if expr == keyword("bottom-up") {
+            Ok(Order::BottomUp)
+        } else {
+            Err(interpreter_err!(
+                CannotConvert,
+                "Rewrite orders are :top-down or :bottom-up",
+                expr
+            ))
+        }
+    }
+}
+
+pub struct Rule {
+    ptn: SExpr,
+    template: SExpr,
+}
+
+impl FromSExpr for Rule {
+    fn from_sexpr(expr: SExpr) -> Result<Rule, InterpreterError> {
+        match expr.clone().as_list().as_deref() {
+            Some([ptn, template]) => Ok(Rule {
+                ptn: ptn.clone(),
+                template: template.clone(),
+            }),
+            Some(_) => Err(interpreter_err!(
+                CannotConvert,
+                "Rewrite rules must be [pattern template] pairs",
+                expr
+            )),
+            None => Err(interpreter_err!(NotA, SExprKind::List, expr)),
+        }
+    }
+}
+
+/// Rewrites `expr` with the first of `rules` to match each subtree until
+/// none of them match, evaluating templates as if written in `scope`
+pub fn rewrite(
+    rules: &[Rule],
+    order: Order,
+    mut expr: SExpr,
+    cxt: &mut Context,
+    scope: usize,
+) -> Result<SExpr, InterpreterError> {
+    let mut rewriter = Rewriter {
+        rules,
+        order,
+        cxt,
+        scope,
+        rewrites: 0,
+    };
+    loop {
+        let (rewritten, changed) = rewriter.pass(expr)?;
+        expr = rewritten;
+        if !changed {
+            return Ok(expr);
+        }
+    }
+}
+
+struct Rewriter<'a> {
+    rules: &'a [Rule],
+    order: Order,
+    cxt: &'a mut Context,
+    scope: usize,
+    /// How many subtrees have been changed so far
+    rewrites: usize,
+}
+
+impl Rewriter<'_> {
+    /// Rewrites `expr` and its children once, returning whether anything
+    /// changed
+    fn pass(&mut self, expr: SExpr) -> Result<(SExpr, bool), InterpreterError> {
+        Ok(match self.order {
+            Order::TopDown => {
+                let (expr, here) = self.apply(expr)?;
+                let (expr, below) = self.children(expr)?;
+                (expr, here || below)
+            }
+            Order::BottomUp => {
+                let (expr, below) = self.children(expr)?;
+                let (expr, here) = self.apply(expr)?;
+                (expr, here || below)
+            }
+        })
+    }
+
+    /// Applies the first rule that matches `expr` and changes it, if any
+    /// does, returning whether one did
+    fn apply(
+        &mut self,
+        expr: SExpr,
+    ) -> Result<(SExpr, bool), InterpreterError> {
+        for rule in self.rules {
+            if let Some(bindings) = rule.ptn.match_ptn(&expr)? {
+                let rewritten = self.cxt.eval_in_block(
+                    self.scope,
+                    &bindings,
+                    &rule.template,
+                )?;
+                // Copies of functions are equal, so a template giving back
+                // a tree holding a pattern or closure is no change
+                if rewritten == expr {
+                    continue;
+                }
+                self.rewrites += 1;
+                if self.rewrites > MAX_REWRITES {
+                    throw_interpreter_err!(
+                        CannotCompute,
+                        "Rewriting didn't stop changing the tree",
+                        expr
+                    )
+                }
+                return Ok((rewritten, true));
+            }
+        }
+        Ok((expr, false))
+    }
+
+    fn children(
+        &mut self,
+        expr: SExpr,
+    ) -> Result<(SExpr, bool), InterpreterError> {
+        use SExpr::*;
+        Ok(match expr {
+            List(ls) => {
+                let mut changed = false;
+                let mut children = Vec::with_capacity(ls.len());
+                for child in ls {
+                    let (child, child_changed) = self.pass(child)?;
+                    changed |= child_changed;
+                    children.push(child);
+                }
+                (List(children), changed)
+            }
+            UnarySigilApp(sigil, inner) => {
+                let (inner, changed) = self.pass(*inner)?;
+                (UnarySigilApp(sigil, Box::new(inner)), changed)
+            }
+            ZeroWidth(inner) => {
+                let (inner, changed) = self.pass(*inner)?;
+                (ZeroWidth(Box::new(inner)), changed)
+            }
+            expr => (expr, false),
+        })
+    }
+}
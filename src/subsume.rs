@@ -0,0 +1,653 @@
+//! Comparing patterns as the sets of values they match.
+//!
+//! Patterns are assumed to match at least one value unless they are an empty
+//! union; patterns that secretly match nothing can give wrong answers.
+
+use crate::context::Context;
+use crate::number;
+use crate::number::Number;
+use crate::parse;
+use crate::{IntoSExpr, KleeneMode, SExpr};
+
+/// An answer to a question about patterns that can't always be decided
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Truth {
+    No,
+    Unknown,
+    Yes,
+}
+
+impl Truth {
+    pub fn and(self, other: Truth) -> Truth {
+        self.min(other)
+    }
+
+    pub fn or(self, other: Truth) -> Truth {
+        self.max(other)
+    }
+
+    pub fn not(self) -> Truth {
+        match self {
+            Truth::Yes => Truth::No,
+            Truth::Unknown => Truth::Unknown,
+            Truth::No => Truth::Yes,
+        }
+    }
+
+    fn all(truths: impl IntoIterator<Item = Truth>) -> Truth {
+        truths.into_iter().fold(Truth::Yes, Truth::and)
+    }
+
+    fn any(truths: impl IntoIterator<Item = Truth>) -> Truth {
+        truths.into_iter().fold(Truth::No, Truth::or)
+    }
+}
+
+impl From<bool> for Truth {
+    fn from(b: bool) -> Truth {
+        if b {
+            Truth::Yes
+        } else {
+            Truth::No
+        }
+    }
+}
+
+impl IntoSExpr for Truth {
+    fn into_sexpr(self) -> SExpr {
+        let name = match self {
+            Truth::Yes => "true",
+            Truth::No => "false",
+            Truth::Unknown => "unknown",
+        };
+        SExpr::UnarySigilApp(':', Box::new(SExpr::Ident(ident!(name))))
+    }
+}
+
+/// One step of a list pattern
+#[derive(Clone, Debug)]
+enum Item {
+    One(SExpr),
+    /// Any number of elements matching the pattern
+    Many(SExpr),
+    /// Matches the next element if it's zero-width, otherwise nothing
+    ZeroWidth(SExpr),
+}
+
+impl SExpr {
+    /// Whether every value this pattern matches is also matched by `sup`
+    pub fn subset_of(&self, sup: &SExpr) -> Truth {
+        // The comparisons take the bindings of a repeated place to be
+        // unrelated, as if the pattern matched more than it does
+        match self.subset_of_unrelated(sup) {
+            Truth::Yes
+                if sup.repeats_places() && self.as_literal().is_none() =>
+            {
+                if !self.repeats_places() && self.separates_repeats_of(sup) {
+                    Truth::No
+                } else {
+                    Truth::Unknown
+                }
+            }
+            Truth::No if self.repeats_places() => Truth::Unknown,
+            truth => truth,
+        }
+    }
+
+    /// Whether this list pattern puts a place, which can be anything, where
+    /// the list pattern `sup` repeats a place, so that it matches lists
+    /// `sup` doesn't. Only lists of singular patterns of the same length are
+    /// compared.
+    fn separates_repeats_of(&self, sup: &SExpr) -> bool {
+        let (sub, sup) = match (self, sup) {
+            (SExpr::List(sub), SExpr::List(sup)) => (sub, sup),
+            _ => return false,
+        };
+        if sub.len() != sup.len()
+            || !sub.iter().chain(sup).all(SExpr::matches_singular)
+            || sub.iter().any(SExpr::is_nothing)
+        {
+            return false;
+        }
+        let is_place = |ptn: &SExpr| matches!(ptn, SExpr::Place(_));
+        sup.iter().enumerate().any(|(i, ptn)| match ptn {
+            SExpr::Place(id) if *id != ident!("#/noread") => {
+                sup.iter().enumerate().skip(i + 1).any(|(j, other)| {
+                    other == ptn && (is_place(&sub[i]) || is_place(&sub[j]))
+                })
+            }
+            _ => false,
+        })
+    }
+
+    fn subset_of_unrelated(&self, sup: &SExpr) -> Truth {
+        use SExpr::*;
+        if self.is_nothing() {
+            return Truth::Yes;
+        }
+        if let Place(_) = sup {
+            return Truth::Yes;
+        }
+        if let Some(value) = self.as_literal() {
+            return sup.matches_value(&value);
+        }
+        match (self, sup) {
+            (Not(sub), Not(sup)) => return sup.subset_of(sub),
+            (sub, Not(excluded)) => return sub.disjoint_from(excluded),
+            // Whatever matches at the root matches somewhere
+            (sub, Somewhere(sup)) if sub.subset_of(sup) == Truth::Yes => {
+                return Truth::Yes
+            }
+            // The predicate of a guard might reject anything
+            (Guard { ptn, .. }, sup) => {
+                return match ptn.subset_of(sup) {
+                    Truth::Yes => Truth::Yes,
+                    _ => Truth::Unknown,
+                }
+            }
+            (sub, Guard { ptn, .. }) => {
+                return match sub.subset_of(ptn) {
+                    Truth::No => Truth::No,
+                    _ => Truth::Unknown,
+                }
+            }
+            _ => {}
+        }
+        if let Some(alternatives) = self.union_alternatives() {
+            return Truth::all(alternatives.iter().map(|a| a.subset_of(sup)));
+        }
+        if let Some(parts) = sup.intersection_parts() {
+            return Truth::all(parts.iter().map(|p| self.subset_of(p)));
+        }
+        if let Some(parts) = self.intersection_parts() {
+            return match Truth::any(parts.iter().map(|p| p.subset_of(sup))) {
+                Truth::Yes => Truth::Yes,
+                _ => Truth::Unknown,
+            };
+        }
+        if let Some(alternatives) = sup.union_alternatives() {
+            let rows = alternatives.iter().map(|a| vec![a.clone()]).collect();
+            return useful(rows, vec![self.clone()]).not();
+        }
+        match (self, sup) {
+            (List(sub), List(sup)) => list_subset(sub, sup),
+            (UnarySigilApp(sub_sig, sub), UnarySigilApp(sup_sig, sup)) => {
+                if sub_sig == sup_sig {
+                    sub.subset_of(sup)
+                } else {
+                    Truth::No
+                }
+            }
+            (ZeroWidth(sub), ZeroWidth(sup)) => sub.subset_of(sup),
+            (Range(sub), Range(sup)) => Truth::from(sub.subset_of(sup)),
+            (Place(_), sup) if sup.shape().is_some() => Truth::No,
+            (sub, sup) => match (sub.shape(), sup.shape()) {
+                (Some(sub), Some(sup)) if sub != sup => Truth::No,
+                _ => Truth::Unknown,
+            },
+        }
+    }
+
+    /// Whether no value is matched by both this pattern and `other`
+    pub fn disjoint_from(&self, other: &SExpr) -> Truth {
+        let literal =
+            self.as_literal().is_some() || other.as_literal().is_some();
+        match self.disjoint_unrelated(other) {
+            Truth::No
+                if !literal
+                    && (self.repeats_places() || other.repeats_places()) =>
+            {
+                Truth::Unknown
+            }
+            truth => truth,
+        }
+    }
+
+    fn disjoint_unrelated(&self, other: &SExpr) -> Truth {
+        use SExpr::*;
+        if self.is_nothing() || other.is_nothing() {
+            return Truth::Yes;
+        }
+        if let (Place(_), _) | (_, Place(_)) = (self, other) {
+            return Truth::No;
+        }
+        match (self.as_literal(), other.as_literal()) {
+            (Some(left), Some(right)) => return Truth::from(left != right),
+            (Some(value), None) => return other.matches_value(&value).not(),
+            (None, Some(value)) => return self.matches_value(&value).not(),
+            (None, None) => {}
+        }
+        if let (Guard { ptn, .. }, other) | (other, Guard { ptn, .. }) =
+            (self, other)
+        {
+            return match ptn.disjoint_from(other) {
+                Truth::Yes => Truth::Yes,
+                _ => Truth::Unknown,
+            };
+        }
+        if let (Not(excluded), other) | (other, Not(excluded)) = (self, other) {
+            // Two negations only miss each other if between them they
+            // exclude everything, which we can't tell
+            if !matches!(other, Not(_)) {
+                return other.subset_of(excluded);
+            }
+        }
+        if let Some(alternatives) = self.union_alternatives() {
+            return Truth::all(
+                alternatives.iter().map(|a| a.disjoint_from(other)),
+            );
+        }
+        if let Some(alternatives) = other.union_alternatives() {
+            return Truth::all(
+                alternatives.iter().map(|a| self.disjoint_from(a)),
+            );
+        }
+        if let Some((parts, other)) = self
+            .intersection_parts()
+            .map(|parts| (parts, other))
+            .or_else(|| other.intersection_parts().map(|parts| (parts, self)))
+        {
+            return match Truth::any(
+                parts.iter().map(|p| p.disjoint_from(other)),
+            ) {
+                Truth::Yes => Truth::Yes,
+                _ => Truth::Unknown,
+            };
+        }
+        match (self, other) {
+            (List(left), List(right)) => match (items(left), items(right)) {
+                (Some(left), Some(right)) => overlap(&left, &right).not(),
+                _ => Truth::Unknown,
+            },
+            (UnarySigilApp(l_sig, left), UnarySigilApp(r_sig, right)) => {
+                if l_sig == r_sig {
+                    left.disjoint_from(right)
+                } else {
+                    Truth::Yes
+                }
+            }
+            (ZeroWidth(left), ZeroWidth(right)) => left.disjoint_from(right),
+            (Range(left), Range(right)) => Truth::from(!left.intersects(right)),
+            (left, right) => match (left.shape(), right.shape()) {
+                (Some(l), Some(r)) if l != r => Truth::Yes,
+                _ => Truth::Unknown,
+            },
+        }
+    }
+
+    /// The numbers this pattern matches, if they form a range
+    pub fn as_range(&self) -> Option<number::Range<Number>> {
+        match self {
+            SExpr::Range(range) => Some((**range).clone()),
+            SExpr::Number(n) => Some(number::Range::new(
+                number::Bound::Inclusive(n.clone()),
+                number::Bound::Inclusive(n.clone()),
+            )),
+            _ => None,
+        }
+    }
+
+    fn matches_value(&self, value: &SExpr) -> Truth {
+        if self.calls_unknown_functions() {
+            return Truth::Unknown;
+        }
+        match self.match_ptn(value) {
+            Ok(bindings) => Truth::from(bindings.is_some()),
+            Err(_) => Truth::Unknown,
+        }
+    }
+
+    /// Whether this is an empty union, which matches nothing
+    pub fn is_nothing(&self) -> bool {
+        self.union_alternatives().is_some_and(<[SExpr]>::is_empty)
+    }
+}
+
+/// The steps of a list pattern, if they can all be understood
+fn items(ptns: &[SExpr]) -> Option<Vec<Item>> {
+    use SExpr::*;
+    let mut steps = Vec::new();
+    for ptn in ptns {
+        match ptn {
+            Consecutive(ptns) => steps.extend(items(ptns)?),
+            Capture { ptn, .. } => {
+                steps.extend(items(std::slice::from_ref(&**ptn))?)
+            }
+            ZeroWidth(inner) => steps.push(Item::ZeroWidth((**inner).clone())),
+            Kleene { mode, .. } if *mode == KleeneMode::Possessive => {
+                // Possessive repetitions can refuse elements the rest of the
+                // list pattern would have matched
+                return None;
+            }
+            Kleene { start, next, .. } => {
+                let mut ptns = start.clone().as_list()?;
+                steps.extend(items(&ptns)?);
+                // Only repetitions of a single pattern are understood
+                let mut next_ptn = || {
+                    let ptn = next
+                        .call(vec![List(ptns.clone())], &mut Context::empty())
+                        .ok()?;
+                    ptns.push(ptn.clone());
+                    Some(ptn)
+                };
+                let (first, second) = (next_ptn()?, next_ptn()?);
+                if first.subset_of(&second) != Truth::Yes
+                    || second.subset_of(&first) != Truth::Yes
+                {
+                    return None;
+                }
+                steps.push(Item::Many(first));
+            }
+            ptn if ptn.matches_singular() => steps.push(Item::One(ptn.clone())),
+            _ => return None,
+        }
+    }
+    Some(steps)
+}
+
+fn zero_width_any() -> SExpr {
+    SExpr::ZeroWidth(Box::new(SExpr::any()))
+}
+
+fn list_subset(sub: &[SExpr], sup: &[SExpr]) -> Truth {
+    let (sub, sup) = match (items(sub), items(sup)) {
+        (Some(sub), Some(sup)) => (sub, sup),
+        _ => return Truth::Unknown,
+    };
+    if sub.iter().chain(&sup).all(|i| matches!(i, Item::One(_))) {
+        return if sub.len() == sup.len() {
+            Truth::all(sub.iter().zip(&sup).map(|(sub, sup)| {
+                match (sub, sup) {
+                    (Item::One(sub), Item::One(sup)) => sub.subset_of(sup),
+                    _ => unreachable!(),
+                }
+            }))
+        } else {
+            Truth::No
+        };
+    }
+    match seq_subset(&sub, &sup) {
+        Truth::Yes => Truth::Yes,
+        _ => {
+            let ((sub_min, sub_max), (sup_min, sup_max)) =
+                (len_range(&sub), len_range(&sup));
+            let too_long = match (sub_max, sup_max) {
+                (None, Some(_)) => true,
+                (Some(sub_max), Some(sup_max)) => sub_max > sup_max,
+                (_, None) => false,
+            };
+            if sub_min < sup_min || too_long || overlap(&sub, &sup) == Truth::No
+            {
+                Truth::No
+            } else {
+                Truth::Unknown
+            }
+        }
+    }
+}
+
+/// The shortest and longest lists matched, `None` being unbounded
+fn len_range(items: &[Item]) -> (usize, Option<usize>) {
+    items
+        .iter()
+        .fold((0, Some(0)), |(min, max), item| match item {
+            Item::One(_) => (min + 1, max.map(|m| m + 1)),
+            Item::ZeroWidth(_) => (min, max.map(|m| m + 1)),
+            Item::Many(_) => (min, None),
+        })
+}
+
+/// Whether there is a way of lining `sub` up with `sup` step by step so that
+/// each step of `sub` is a subset of the steps it is lined up with. `No` only
+/// means that no such way was found.
+fn seq_subset(sub: &[Item], sup: &[Item]) -> Truth {
+    use Item::*;
+    match (sub.first(), sup.first()) {
+        (None, None) => Truth::Yes,
+        (None, Some(Many(_))) | (None, Some(ZeroWidth(_))) => {
+            seq_subset(sub, &sup[1..])
+        }
+        (None, Some(One(_))) | (Some(_), None) => Truth::No,
+        (Some(One(l)), Some(One(r))) => {
+            l.subset_of(r).and(seq_subset(&sub[1..], &sup[1..]))
+        }
+        (Some(One(l)), Some(Many(r))) => l
+            .subset_of(r)
+            .and(seq_subset(&sub[1..], sup))
+            .or(seq_subset(sub, &sup[1..])),
+        (Some(One(l)), Some(ZeroWidth(r))) => l
+            .subset_of(&SExpr::ZeroWidth(Box::new(r.clone())))
+            .and(seq_subset(&sub[1..], &sup[1..]))
+            .or(l
+                .disjoint_from(&zero_width_any())
+                .and(seq_subset(sub, &sup[1..]))),
+        (Some(Many(l)), Some(Many(r))) => l
+            .subset_of(r)
+            .and(seq_subset(&sub[1..], sup))
+            .or(seq_subset(sub, &sup[1..])),
+        (Some(Many(l)), Some(ZeroWidth(_))) => l
+            .disjoint_from(&zero_width_any())
+            .and(seq_subset(sub, &sup[1..])),
+        (Some(ZeroWidth(l)), Some(ZeroWidth(r))) => {
+            l.subset_of(r).and(seq_subset(&sub[1..], &sup[1..]))
+        }
+        (Some(ZeroWidth(l)), Some(Many(r))) => {
+            SExpr::ZeroWidth(Box::new(l.clone()))
+                .subset_of(r)
+                .and(seq_subset(&sub[1..], sup))
+                .or(seq_subset(sub, &sup[1..]))
+        }
+        (Some(Many(_)), Some(One(_))) | (Some(ZeroWidth(_)), Some(One(_))) => {
+            Truth::No
+        }
+    }
+}
+
+/// Whether some list is matched by both sequences of steps
+fn overlap(left: &[Item], right: &[Item]) -> Truth {
+    use Item::*;
+    match (left.first(), right.first()) {
+        (None, None) => Truth::Yes,
+        (None, Some(Many(_))) | (None, Some(ZeroWidth(_))) => {
+            overlap(left, &right[1..])
+        }
+        (Some(Many(_)), None) | (Some(ZeroWidth(_)), None) => {
+            overlap(&left[1..], right)
+        }
+        (None, Some(One(_))) | (Some(One(_)), None) => Truth::No,
+        (Some(One(l)), Some(One(r))) => l
+            .disjoint_from(r)
+            .not()
+            .and(overlap(&left[1..], &right[1..])),
+        (Some(One(l)), Some(Many(r))) => l
+            .disjoint_from(r)
+            .not()
+            .and(overlap(&left[1..], right))
+            .or(overlap(left, &right[1..])),
+        (Some(Many(l)), Some(One(r))) => l
+            .disjoint_from(r)
+            .not()
+            .and(overlap(left, &right[1..]))
+            .or(overlap(&left[1..], right)),
+        (Some(ZeroWidth(l)), Some(ZeroWidth(r))) => l
+            .disjoint_from(r)
+            .not()
+            .and(overlap(&left[1..], &right[1..]))
+            .or(overlap(&left[1..], &right[1..])),
+        (Some(ZeroWidth(_)), Some(_)) => overlap(&left[1..], right),
+        (Some(_), Some(ZeroWidth(_))) => overlap(left, &right[1..]),
+        (Some(Many(_)), Some(Many(_))) => {
+            overlap(&left[1..], right).or(overlap(left, &right[1..]))
+        }
+    }
+}
+
+/// Whether some value matched by every pattern of `row` is matched by none of
+/// the rows of `rows` (in the sense of pattern usefulness, with each row a
+/// tuple of patterns)
+pub fn useful(rows: Vec<Vec<SExpr>>, row: Vec<SExpr>) -> Truth {
+    // A row binding a place more than once only covers values where the
+    // bindings are equal, which columns can't express, so it's taken to
+    // cover nothing
+    let rows = rows
+        .into_iter()
+        .filter(|r| !SExpr::List(r.clone()).repeats_places())
+        .collect();
+    useful_unrelated(rows, row)
+}
+
+/// `useful`, taking the bindings of a repeated place to be unrelated
+fn useful_unrelated(rows: Vec<Vec<SExpr>>, row: Vec<SExpr>) -> Truth {
+    use SExpr::*;
+    let (first, rest) = match row.split_first() {
+        Some(it) => it,
+        None => return Truth::from(rows.is_empty()),
+    };
+    if let Some(alternatives) = first.union_alternatives() {
+        return Truth::any(alternatives.iter().map(|alt| {
+            let mut row = vec![alt.clone()];
+            row.extend_from_slice(rest);
+            useful_unrelated(rows.clone(), row)
+        }));
+    }
+    let mut expanded = Vec::new();
+    for r in rows {
+        match r[0].union_alternatives() {
+            Some(alternatives) => {
+                for alt in alternatives {
+                    let mut r = r.clone();
+                    r[0] = alt.clone();
+                    expanded.push(r);
+                }
+            }
+            None => expanded.push(r),
+        }
+    }
+    // A row that we can't specialize might still cover the rest, so the
+    // answer can only be `Yes` if none were skipped
+    let mut uncertain = false;
+    let mut specialized = Vec::new();
+    let specialize = |arity: usize, subs: Option<Vec<SExpr>>, r: &[SExpr]| {
+        let mut out = subs.unwrap_or_else(|| vec![SExpr::any(); arity]);
+        out.extend_from_slice(&r[1..]);
+        out
+    };
+    let row = match first {
+        Place(_) => {
+            for r in &expanded {
+                if let Place(_) = r[0] {
+                    specialized.push(r[1..].to_vec());
+                } else if r[0].shape().is_none() {
+                    uncertain = true;
+                }
+            }
+            rest.to_vec()
+        }
+        List(ptns) if ptns.iter().all(SExpr::matches_singular) => {
+            for r in &expanded {
+                match &r[0] {
+                    Place(_) => {
+                        specialized.push(specialize(ptns.len(), None, r))
+                    }
+                    List(sub) if sub.iter().all(SExpr::matches_singular) => {
+                        if sub.len() == ptns.len() {
+                            specialized.push(specialize(
+                                0,
+                                Some(sub.clone()),
+                                r,
+                            ))
+                        }
+                    }
+                    other
+                        if other.shape().is_none()
+                            || other.shape() == first.shape() =>
+                    {
+                        uncertain = true
+                    }
+                    _ => {}
+                }
+            }
+            let mut row = ptns.clone();
+            row.extend_from_slice(rest);
+            row
+        }
+        UnarySigilApp(sigil, inner) => {
+            for r in &expanded {
+                match &r[0] {
+                    Place(_) => specialized.push(specialize(1, None, r)),
+                    UnarySigilApp(s, sub) if s == sigil => specialized
+                        .push(specialize(0, Some(vec![(**sub).clone()]), r)),
+                    other if other.shape().is_none() => uncertain = true,
+                    _ => {}
+                }
+            }
+            let mut row = vec![(**inner).clone()];
+            row.extend_from_slice(rest);
+            row
+        }
+        Range(range) => {
+            // Split the range where a row covers only part of it
+            for r in &expanded {
+                if let Some(other) = r[0].as_range() {
+                    if !range.subset_of(&other) && range.intersects(&other) {
+                        let pieces = range
+                            .intersection(&other)
+                            .into_iter()
+                            .chain(range.minus(&other));
+                        return Truth::any(pieces.map(|piece| {
+                            let mut row = vec![Range(Box::new(piece))];
+                            row.extend_from_slice(rest);
+                            useful_unrelated(expanded.clone(), row)
+                        }));
+                    }
+                }
+            }
+            for r in &expanded {
+                match (&r[0], r[0].as_range()) {
+                    (Place(_), _) => specialized.push(r[1..].to_vec()),
+                    (_, Some(other)) if range.subset_of(&other) => {
+                        specialized.push(r[1..].to_vec())
+                    }
+                    (_, Some(_)) => {}
+                    (other, None)
+                        if other.shape().is_none()
+                            || other.shape() == first.shape() =>
+                    {
+                        uncertain = true
+                    }
+                    _ => {}
+                }
+            }
+            rest.to_vec()
+        }
+        Not(excluded) => {
+            // Everything outside of `excluded`, which only rows matching
+            // anything are sure to cover
+            for r in &expanded {
+                match &r[0] {
+                    Place(_) => specialized.push(r[1..].to_vec()),
+                    other if other.subset_of(excluded) == Truth::Yes => {}
+                    _ => uncertain = true,
+                }
+            }
+            rest.to_vec()
+        }
+        first => match first.as_literal() {
+            Some(value) => {
+                for r in &expanded {
+                    match r[0].matches_value(&value) {
+                        Truth::Yes => specialized.push(r[1..].to_vec()),
+                        Truth::No => {}
+                        Truth::Unknown => uncertain = true,
+                    }
+                }
+                rest.to_vec()
+            }
+            None => return Truth::Unknown,
+        },
+    };
+    match useful_unrelated(specialized, row) {
+        Truth::Yes if uncertain => Truth::Unknown,
+        result => result,
+    }
+}